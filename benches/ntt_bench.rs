@@ -21,7 +21,7 @@ fn bench_ntt_calc(c: &mut Criterion) {
     log::info!("Starting set NTT data");
     let _ = driver.set_data(NTTInput {
         buf_host,
-        data: in_vec,
+        data: &in_vec,
     });
     log::info!("Successfully set NTT data");
     let _ = driver.driver_client.initialize_cms();
@@ -6,12 +6,19 @@
 //! for custom modules. Each custom module is built on top of this foundation and
 //! includes its own specific fields and methods.
 //!
-use super::{dclient_cfg::*, dclient_code::*};
+use super::{
+    dclient_buffer::DmaBufferPool, dclient_cfg::*, dclient_code::*, dclient_mmap::RegisterMap,
+};
 use crate::{
     error::*,
-    utils::{open_channel, AccessFlags},
+    utils::{open_channel, try_open_channel, write_all_vectored_at, AccessFlags},
+};
+use std::{
+    fmt::Debug,
+    os::unix::fs::FileExt,
+    thread::{self, sleep},
+    time::Duration,
 };
-use std::{fmt::Debug, os::unix::fs::FileExt, thread::sleep, time::Duration};
 
 /// A trait for defining functions related to parameters of specific core image.
 pub trait ParametersAPI {
@@ -54,8 +61,17 @@ pub struct DriverClient {
     pub dma_h2c_write: std::fs::File,
     /// Read only channel from core using DMA bus.
     pub dma_c2h_read: std::fs::File,
+    /// Additional write channels `h2c_1..h2c_N`, used to stripe large transfers.
+    pub dma_h2c_extra: Vec<std::fs::File>,
+    /// Additional read channels `c2h_1..c2h_N`, used to stripe large transfers.
+    pub dma_c2h_extra: Vec<std::fs::File>,
     /// Read and write file descriptor for working with a register space that uses AXI-lite protocol.
     pub ctrl: std::fs::File,
+    /// Memory mapping of the register space. When available, register accesses are done
+    /// with volatile loads and stores instead of syscalls on [`DriverClient::ctrl`].
+    pub ctrl_map: Option<RegisterMap>,
+    /// Pool of page-aligned host buffers for DMA transfers, shared by primitives on top of the client.
+    pub buffer_pool: DmaBufferPool,
 }
 
 impl DriverClient {
@@ -77,13 +93,31 @@ impl DriverClient {
     /// let dclient = DriverClient::new("0", DriverConfig::driver_client_cfg(CardType::C1100));
     /// ```
     pub fn new(id: &str, cfg: DriverConfig) -> Self {
+        let ctrl = open_channel(&format!("/dev/xdma{}_user", id), AccessFlags::RdwrMode);
+        let ctrl_map = RegisterMap::new(&ctrl, cfg.ctrl_bar_size as usize)
+            .map_err(|e| log::warn!("Register space is not mapped, fallback to syscalls: {}", e))
+            .ok();
         DriverClient {
             cfg,
             dma_h2c_write: open_channel(&format!("/dev/xdma{}_h2c_0", id), AccessFlags::WrMode),
             dma_c2h_read: open_channel(&format!("/dev/xdma{}_c2h_0", id), AccessFlags::RdMode),
-            ctrl: open_channel(&format!("/dev/xdma{}_user", id), AccessFlags::RdwrMode),
+            dma_h2c_extra: open_extra_channels(id, "h2c", cfg.dma_channels, AccessFlags::WrMode),
+            dma_c2h_extra: open_extra_channels(id, "c2h", cfg.dma_channels, AccessFlags::RdMode),
+            ctrl,
+            ctrl_map,
+            buffer_pool: DmaBufferPool::new(cfg.dma_buffer_cfg),
         }
     }
+
+    /// Returns the number of opened DMA channels in each direction.
+    pub fn dma_channels(&self) -> usize {
+        1 + self.dma_h2c_extra.len().min(self.dma_c2h_extra.len())
+    }
+
+    /// Returns the register mapping if the access of `len` bytes at `addr` can be served by it.
+    fn mapped(&self, addr: u64, len: usize) -> Option<&RegisterMap> {
+        self.ctrl_map.as_ref().filter(|map| map.contains(addr, len))
+    }
     /// The `reset` method resets the driver primitive to its initial state.
     pub fn reset(&self) -> Result<()> {
         self.set_dfx_decoupling(1)?;
@@ -306,12 +340,7 @@ impl DriverClient {
         offset: T,
     ) -> Result<u32> {
         let mut task_label = [0, 0, 0, 0];
-        self.ctrl
-            .read_exact_at(&mut task_label, base_address + offset.into())
-            .map_err(|e| DriverClientError::ReadError {
-                offset: format!("{:?}", offset),
-                source: e,
-            })?;
+        self.ctrl_read(base_address, offset, &mut task_label)?;
         let res = u32::from_le_bytes(task_label);
         log::debug!("Getting data [ {:#X?} ] from label {:?}", res, offset);
         Ok(res)
@@ -331,9 +360,16 @@ impl DriverClient {
         base_address: u64,
         offset: T,
     ) -> Result<u64> {
+        let addr = base_address + offset.into();
+        if let Some(map) = self.mapped(addr, 8) {
+            let res = map.read_u64(addr)?;
+            log::debug!("Getting data [ {:#X?} ] from label {:?}", res, offset);
+            return Ok(res);
+        }
+
         let mut task_label_0 = [0, 0, 0, 0];
         self.ctrl
-            .read_exact_at(&mut task_label_0, base_address + offset.into())
+            .read_exact_at(&mut task_label_0, addr)
             .map_err(|e| DriverClientError::ReadError {
                 offset: format!("{:?}", offset),
                 source: e,
@@ -341,7 +377,7 @@ impl DriverClient {
 
         let mut task_label_1 = [0, 0, 0, 0];
         self.ctrl
-            .read_exact_at(&mut task_label_1, base_address + offset.into() + 4)
+            .read_exact_at(&mut task_label_1, addr + 4)
             .map_err(|e| DriverClientError::ReadError {
                 offset: format!("{:?}", offset),
                 source: e,
@@ -381,13 +417,18 @@ impl DriverClient {
         offset: T,
         data: u32,
     ) -> Result<()> {
-        let bytes_array = data.to_le_bytes();
-        self.ctrl
-            .write_all_at(&bytes_array, base_address + offset.into())
-            .map_err(|e| DriverClientError::WriteError {
-                offset: format!("{:?}", offset),
-                source: e,
+        let addr = base_address + offset.into();
+        if let Some(map) = self.mapped(addr, 4) {
+            map.write_u32(addr, data)?;
+        } else {
+            let bytes_array = data.to_le_bytes();
+            self.ctrl.write_all_at(&bytes_array, addr).map_err(|e| {
+                DriverClientError::WriteError {
+                    offset: format!("{:?}", offset),
+                    source: e,
+                }
             })?;
+        }
 
         log::debug!("Successfully set data [ {:?} ] to label {:?}", data, offset);
         Ok(())
@@ -411,19 +452,57 @@ impl DriverClient {
         offset: T,
         data: &[u8],
     ) -> Result<()> {
-        data.chunks(4).enumerate().try_for_each(|(i, s_chunk)| {
-            self.ctrl
-                .write_all_at(s_chunk, base_address + offset.into() + (i * 4) as u64)
-                .map_err(|e| DriverClientError::WriteError {
-                    offset: format!("{:?}", offset),
-                    source: e,
-                })
-        })?;
+        let addr = base_address + offset.into();
+        if let Some(map) = self.mapped(addr, data.len()) {
+            map.write_from(addr, data)?;
+        } else {
+            data.chunks(4).enumerate().try_for_each(|(i, s_chunk)| {
+                self.ctrl
+                    .write_all_at(s_chunk, addr + (i * 4) as u64)
+                    .map_err(|e| DriverClientError::WriteError {
+                        offset: format!("{:?}", offset),
+                        source: e,
+                    })
+            })?;
+        }
 
         crate::setter_log!(data, offset);
         Ok(())
     }
 
+    /// The method for reading consecutive registers using the axil interface at a given adress and offset.
+    /// Unlike [`DriverClient::ctrl_read_u32`] it does not log the value,
+    /// so it is suitable for polling loops and for reading multi-register values.
+    ///
+    /// # Arguments
+    ///
+    /// * `base_address`: the base address in the CTRL bus addresses space
+    /// * `offset`: an enum which represent the specific offset for given `base_address`.
+    /// * `read_buffer`: existing memory for reading data, its length defines the number of bytes to read.
+    ///
+    pub fn ctrl_read<T: Debug + Into<u64> + Copy>(
+        &self,
+        base_address: u64,
+        offset: T,
+        read_buffer: &mut [u8],
+    ) -> Result<()> {
+        let addr = base_address + offset.into();
+        if let Some(map) = self.mapped(addr, read_buffer.len()) {
+            return map.read_into(addr, read_buffer);
+        }
+        read_buffer
+            .chunks_mut(4)
+            .enumerate()
+            .try_for_each(|(i, r_chunk)| {
+                self.ctrl
+                    .read_exact_at(r_chunk, addr + (i * 4) as u64)
+                    .map_err(|e| DriverClientError::ReadError {
+                        offset: format!("{:?}", offset),
+                        source: e,
+                    })
+            })
+    }
+
     // ==== XDMA DMA BUS ====
     /// The method for reading data from FPGA by DMA bus
     /// It returns a `Vec<u8>` containing the read data, allowing the user to further process the data.
@@ -431,13 +510,14 @@ impl DriverClient {
     /// The location is determined by adding the `offset` to the `base_address`.
     /// If you don't need an offset for reading, use the default offset [`DMA_RW::OFFSET`].
     ///
+    /// The data is read through the first C2H channel, so the method is suitable for stream targets.
+    /// Use [`DriverClient::dma_read_striped`] for large reads from memory.
+    ///
     /// # Arguments
     ///
     /// * `base_address`: the base address in the DMA bus addresses space
     /// * `offset`: an enum which represent the specific offset for given `base_address`.
-    /// * `read_buffer`: existing mememory for reading data
-    ///
-    /// returns: Vec<u8>
+    /// * `read_buffer`: existing mememory for reading data, e.g. a `Vec<u8>` or a [`DmaBuffer`](super::DmaBuffer)
     ///
     /// # Examples
     ///
@@ -457,7 +537,7 @@ impl DriverClient {
         &self,
         base_address: u64,
         offset: T,
-        read_buffer: &mut Vec<u8>,
+        read_buffer: &mut [u8],
     ) -> Result<()> {
         self.dma_c2h_read
             .read_exact_at(read_buffer, base_address + offset.into())
@@ -470,12 +550,62 @@ impl DriverClient {
         Ok(())
     }
 
+    /// Reads data from a memory target (e.g. HBM) like [`DriverClient::dma_read`].
+    ///
+    /// Reads of at least `dma_stripe_min_size` bytes are split into page-aligned stripes
+    /// which are read in parallel through all opened C2H channels.
+    /// Stripes are read from different addresses, so the method must not be used for stream targets.
+    pub fn dma_read_striped<T: Debug + Into<u64> + Copy>(
+        &self,
+        base_address: u64,
+        offset: T,
+        read_buffer: &mut [u8],
+    ) -> Result<()> {
+        let addr = base_address + offset.into();
+        let len = read_buffer.len();
+        if !is_striped(len, self.cfg.dma_stripe_min_size, &self.dma_c2h_extra) {
+            return self.dma_read(base_address, offset, read_buffer);
+        }
+        let channels: Vec<&std::fs::File> = std::iter::once(&self.dma_c2h_read)
+            .chain(self.dma_c2h_extra.iter())
+            .collect();
+        let stripe = stripe_size(len, channels.len());
+        log::trace!(
+            "Read {} bytes by {} stripes of {}",
+            len,
+            channels.len(),
+            stripe
+        );
+        thread::scope(|s| {
+            read_buffer
+                .chunks_mut(stripe)
+                .zip(channels)
+                .enumerate()
+                .map(|(i, (chunk, ch))| {
+                    s.spawn(move || ch.read_exact_at(chunk, addr + (i * stripe) as u64))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .try_for_each(|h| h.join().unwrap())
+        })
+        .map_err(|e| DriverClientError::ReadError {
+            offset: format!("{:?}", offset),
+            source: e,
+        })?;
+
+        crate::getter_log!(read_buffer, offset);
+        Ok(())
+    }
+
     /// The method for writing data from host memory into FPGA.
     ///
     /// The location is determined by adding the `offset` to the `base_address`.
     /// If you don't need an offset for reading, use the default offset for this:
     /// [`DMA_RW::OFFSET`]
     ///
+    /// The data is written through the first H2C channel, so the method is suitable for stream targets.
+    /// Use [`DriverClient::dma_write_striped`] for large writes into memory.
+    ///
     /// # Arguments
     ///
     /// * `base_address`: the base address in the DMA bus addresses space
@@ -516,6 +646,53 @@ impl DriverClient {
         Ok(())
     }
 
+    /// Writes data into a memory target (e.g. HBM) like [`DriverClient::dma_write`].
+    ///
+    /// Writes of at least `dma_stripe_min_size` bytes are split into page-aligned stripes
+    /// which are written in parallel through all opened H2C channels.
+    /// Stripes go to different addresses and may arrive in any order,
+    /// so the method must not be used for stream targets.
+    pub fn dma_write_striped<T: Debug + Into<u64> + Copy>(
+        &self,
+        base_address: u64,
+        offset: T,
+        data: &[u8],
+    ) -> Result<()> {
+        if !self.is_write_striped(data.len()) {
+            return self.dma_write(base_address, offset, data);
+        }
+        let addr = base_address + offset.into();
+        let channels: Vec<&std::fs::File> = std::iter::once(&self.dma_h2c_write)
+            .chain(self.dma_h2c_extra.iter())
+            .collect();
+        let stripe = stripe_size(data.len(), channels.len());
+        log::trace!("Write by {} stripes of {}", channels.len(), stripe);
+        thread::scope(|s| {
+            data.chunks(stripe)
+                .zip(channels)
+                .enumerate()
+                .map(|(i, (chunk, ch))| {
+                    s.spawn(move || ch.write_all_at(chunk, addr + (i * stripe) as u64))
+                })
+                .collect::<Vec<_>>()
+                .into_iter()
+                .try_for_each(|h| h.join().unwrap())
+        })
+        .map_err(|e| DriverClientError::WriteError {
+            offset: format!("{:?}", offset),
+            source: e,
+        })?;
+        log::trace!("Write data of size {}", data.len());
+
+        crate::setter_log!(data, offset);
+        Ok(())
+    }
+
+    /// Whether [`DriverClient::dma_write_striped`] splits a write of `len` bytes across channels.
+    pub(crate) fn is_write_striped(&self, len: usize) -> bool {
+        is_striped(len, self.cfg.dma_stripe_min_size, &self.dma_h2c_extra)
+    }
+
     /// This method writes data by chunks to a specific location in the DMA.
     /// The location is determined by adding the `offset` to the `base_address`.
     /// If you don't need an offset to write to the dma,
@@ -563,6 +740,60 @@ impl DriverClient {
         Ok(())
     }
 
+    /// This method writes a list of segments from host memory into FPGA by DMA bus.
+    /// Each segment is a pair of an absolute address in the DMA bus addresses space
+    /// and a byte slice to be written there. Segments are written in the given order.
+    ///
+    /// Consecutive segments whose addresses are contiguous are submitted by a single
+    /// vectored write, so the data is never copied into an intermediate buffer.
+    /// All segments go through the first H2C channel, so the order of data is preserved
+    /// and the method is suitable for stream targets.
+    ///
+    /// # Arguments
+    ///
+    /// * `segments`: a list of `(address, data)` pairs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// use ingo_blaze::driver_client::*;
+    ///
+    /// let dclient = DriverClient::new("0", DriverConfig::driver_client_cfg(CardType::C1100));
+    /// let scalars = vec![1, 2, 3, 4];
+    /// let points = vec![5, 6, 7, 8];
+    ///
+    /// dclient.dma_write_vectored(&[(0x0, scalars.as_slice()), (0x1000, points.as_slice())]);
+    /// ```
+    pub fn dma_write_vectored(&self, segments: &[(u64, &[u8])]) -> Result<()> {
+        let mut start = 0;
+        while start < segments.len() {
+            let addr = segments[start].0;
+            let mut next_addr = addr + segments[start].1.len() as u64;
+            let mut end = start + 1;
+            while end < segments.len() && segments[end].0 == next_addr {
+                next_addr += segments[end].1.len() as u64;
+                end += 1;
+            }
+
+            let bufs: Vec<&[u8]> = segments[start..end].iter().map(|s| s.1).collect();
+            log::trace!(
+                "Trying to write {} segments of size {} to {:#X?}",
+                bufs.len(),
+                next_addr - addr,
+                addr
+            );
+            write_all_vectored_at(&self.dma_h2c_write, &bufs, addr).map_err(|e| {
+                DriverClientError::WriteError {
+                    offset: format!("{:#X?}", addr),
+                    source: e,
+                }
+            })?;
+            start = end;
+        }
+        log::trace!("Write {} segments", segments.len());
+        Ok(())
+    }
+
     pub fn firewalls_status(&self) {
         let mut ret = self.ctrl_read_u32(
             self.cfg.ctrl_hbicap_baseaddr,
@@ -590,3 +821,93 @@ impl DriverClient {
         res == value
     }
 }
+
+/// Opens DMA channels from `1` to `nof_channels - 1` in the given direction.
+/// Opening stops at the first channel which is not exposed by the driver.
+fn open_extra_channels(
+    id: &str,
+    direction: &str,
+    nof_channels: usize,
+    mode: AccessFlags,
+) -> Vec<std::fs::File> {
+    let mut channels = Vec::new();
+    for ch in 1..nof_channels {
+        let path = format!("/dev/xdma{}_{}_{}", id, direction, ch);
+        match try_open_channel(&path, mode) {
+            Ok(file) => channels.push(file),
+            Err(e) => {
+                log::warn!("Failed to open DMA channel {}: {}", path, e);
+                break;
+            }
+        }
+    }
+    channels
+}
+
+/// Whether a memory transfer of `len` bytes is split between the first and `extra` channels.
+fn is_striped(len: usize, stripe_min_size: usize, extra: &[std::fs::File]) -> bool {
+    len >= stripe_min_size && !extra.is_empty()
+}
+
+/// Size of one stripe when `len` bytes are split between `nof_channels`, aligned to a page.
+fn stripe_size(len: usize, nof_channels: usize) -> usize {
+    const ALIGN: usize = 4096;
+    len.div_ceil(nof_channels).div_ceil(ALIGN) * ALIGN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{fs::OpenOptions, path::PathBuf};
+
+    /// A regular file in place of a DMA channel, so transfers can be inspected.
+    fn channel(name: &str) -> (PathBuf, std::fs::File) {
+        let path = std::env::temp_dir().join(format!("ingo_dma_{}_{}", std::process::id(), name));
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn stream_transfers_are_never_striped() {
+        let cfg = DriverConfig::driver_client_cfg(CardType::C1100)
+            .with_dma_channels(2)
+            .with_dma_stripe_min_size(4096);
+        let (h2c_0, h2c_0_file) = channel("h2c_0");
+        let (h2c_1, h2c_1_file) = channel("h2c_1");
+        let (c2h_0, c2h_0_file) = channel("c2h_0");
+        let (ctrl, ctrl_file) = channel("ctrl");
+        let dclient = DriverClient {
+            cfg,
+            dma_h2c_write: h2c_0_file,
+            dma_c2h_read: c2h_0_file,
+            dma_h2c_extra: vec![h2c_1_file],
+            dma_c2h_extra: vec![],
+            ctrl: ctrl_file,
+            ctrl_map: None,
+            buffer_pool: DmaBufferPool::new(cfg.dma_buffer_cfg),
+        };
+        let data: Vec<u8> = (0..3 * 4096).map(|i| i as u8).collect();
+
+        // A stream target gets the whole transfer in order through the first channel.
+        dclient.dma_write(0, 0u64, &data).unwrap();
+        assert_eq!(std::fs::read(&h2c_0).unwrap(), data);
+        assert!(std::fs::read(&h2c_1).unwrap().is_empty());
+
+        // Memory is split into page-aligned stripes at their own addresses.
+        dclient.dma_write_striped(0x10000, 0u64, &data).unwrap();
+        let first = std::fs::read(&h2c_0).unwrap();
+        let second = std::fs::read(&h2c_1).unwrap();
+        assert_eq!(first[0x10000..], data[..2 * 4096]);
+        assert_eq!(second[0x10000 + 2 * 4096..], data[2 * 4096..]);
+
+        for path in [h2c_0, h2c_1, c2h_0, ctrl] {
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
@@ -0,0 +1,316 @@
+//! Futures for working with the device from async code.
+//!
+//! The futures do not depend on a particular runtime. Every poll does a bounded amount of
+//! blocking IO (a register read or one piece of a DMA transfer through a single channel)
+//! and yields back to the executor, so a long transfer doesn't occupy an executor thread.
+//! A future waiting for the device is woken by a timer thread with a growing back-off,
+//! so the executor thread is free and the register space is not polled in a tight loop.
+use super::DriverClient;
+use crate::error::*;
+
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    task::{Context, Poll, Waker},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// The largest piece of a DMA transfer written in one poll.
+/// It is well below the default size of striped transfers, so a poll never spawns stripe threads.
+pub const ASYNC_DMA_PIECE_SIZE: usize = 1 << 22;
+
+/// The first delay before the device is polled again, it doubles on every poll.
+pub const POLL_MIN_INTERVAL: Duration = Duration::from_micros(10);
+/// The longest delay between polls of the device.
+pub const POLL_MAX_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A future which polls the device until `f` returns a value.
+///
+/// `f` is called once per poll. If it returns `Ok(None)`, the future asks the timer thread
+/// to wake it after a delay, from [`POLL_MIN_INTERVAL`] up to [`POLL_MAX_INTERVAL`].
+pub struct PollDevice<F> {
+    f: F,
+    interval: Duration,
+}
+
+/// Creates a [`PollDevice`] future.
+pub fn poll_device<T, F>(f: F) -> PollDevice<F>
+where
+    F: FnMut() -> Result<Option<T>> + Unpin,
+{
+    PollDevice {
+        f,
+        interval: POLL_MIN_INTERVAL,
+    }
+}
+
+impl<T, F> Future for PollDevice<F>
+where
+    F: FnMut() -> Result<Option<T>> + Unpin,
+{
+    type Output = Result<T>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match (self.f)() {
+            Ok(Some(value)) => Poll::Ready(Ok(value)),
+            Ok(None) => {
+                wake_after(cx.waker().clone(), self.interval);
+                self.interval = (self.interval * 2).min(POLL_MAX_INTERVAL);
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// Wakes `waker` after `delay` from a timer thread shared by all futures of the module.
+fn wake_after(waker: Waker, delay: Duration) {
+    static TIMER: OnceLock<Sender<(Instant, Waker)>> = OnceLock::new();
+    let timer = TIMER.get_or_init(|| {
+        let (tx, rx) = mpsc::channel();
+        thread::Builder::new()
+            .name("ingo-poll-timer".to_string())
+            .spawn(move || run_timer(rx))
+            .expect("failed to start the poll timer thread");
+        tx
+    });
+    // The thread never stops, so the receiver is always alive.
+    timer.send((Instant::now() + delay, waker)).unwrap();
+}
+
+fn run_timer(rx: Receiver<(Instant, Waker)>) {
+    let mut pending: Vec<(Instant, Waker)> = Vec::new();
+    loop {
+        let now = Instant::now();
+        pending.retain(|(at, waker)| {
+            let due = *at <= now;
+            if due {
+                waker.wake_by_ref();
+            }
+            !due
+        });
+        let received = match pending.iter().map(|(at, _)| *at).min() {
+            Some(at) => rx.recv_timeout(at.saturating_duration_since(Instant::now())),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok(entry) => pending.push(entry),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// A future which writes segments by DMA, at most [`ASYNC_DMA_PIECE_SIZE`] bytes per poll.
+///
+/// Segments are added with [`WriteSegments::memory`] and [`WriteSegments::stream`]
+/// and written in the order they were added.
+/// A stream target (e.g. an input FIFO of a core) must never see a part of the data, so if
+/// the future is dropped in the middle of a started transfer which has stream segments,
+/// the rest of the transfer is written synchronously by the destructor, which blocks
+/// the dropping thread until the stream is complete. A transfer to memory only is left partially written,
+/// the data must be set again before the next task.
+pub struct WriteSegments<'a> {
+    client: &'a DriverClient,
+    /// Pieces of segments with a flag of a stream target.
+    pieces: Vec<(u64, &'a [u8], bool)>,
+    next: usize,
+}
+
+impl<'a> WriteSegments<'a> {
+    pub fn new(client: &'a DriverClient) -> Self {
+        WriteSegments {
+            client,
+            pieces: Vec::new(),
+            next: 0,
+        }
+    }
+
+    /// Adds segments of memory targets, each piece is written through a single channel,
+    /// see [`DriverClient::dma_write`].
+    pub fn memory(mut self, segments: &[(u64, &'a [u8])]) -> Self {
+        for &(addr, data) in segments {
+            for (i, piece) in data.chunks(ASYNC_DMA_PIECE_SIZE).enumerate() {
+                let offset = (i * ASYNC_DMA_PIECE_SIZE) as u64;
+                self.pieces.push((addr + offset, piece, false));
+            }
+        }
+        self
+    }
+
+    /// Adds segments of stream targets, they are written in order through a single channel,
+    /// see [`DriverClient::dma_write_vectored`].
+    pub fn stream(mut self, segments: &[(u64, &'a [u8])]) -> Self {
+        for &(addr, data) in segments {
+            // Every piece of a stream goes to the same address as the whole segment.
+            for piece in data.chunks(ASYNC_DMA_PIECE_SIZE) {
+                self.pieces.push((addr, piece, true));
+            }
+        }
+        self
+    }
+
+    fn is_done(&self) -> bool {
+        self.next == self.pieces.len()
+    }
+
+    /// Writes the next piece, consecutive stream pieces are written together up to the size of a piece.
+    fn write_next(&mut self) -> Result<()> {
+        let start = self.next;
+        let (addr, data, stream) = self.pieces[start];
+        let mut end = start + 1;
+        if stream {
+            let mut size = data.len();
+            while end < self.pieces.len()
+                && self.pieces[end].2
+                && size + self.pieces[end].1.len() <= ASYNC_DMA_PIECE_SIZE
+            {
+                size += self.pieces[end].1.len();
+                end += 1;
+            }
+            let segments: Vec<(u64, &[u8])> = self.pieces[start..end]
+                .iter()
+                .map(|&(addr, data, _)| (addr, data))
+                .collect();
+            self.client.dma_write_vectored(&segments)?;
+        } else {
+            self.client.dma_write(addr, 0u64, data)?;
+        }
+        self.next = end;
+        Ok(())
+    }
+}
+
+impl Future for WriteSegments<'_> {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.is_done() {
+            return Poll::Ready(Ok(()));
+        }
+        if let Err(e) = self.write_next() {
+            // Nothing is left to flush after a failed transfer.
+            self.next = self.pieces.len();
+            return Poll::Ready(Err(e));
+        }
+        if self.is_done() {
+            Poll::Ready(Ok(()))
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for WriteSegments<'_> {
+    fn drop(&mut self) {
+        // A transfer which was not started leaves nothing to complete.
+        if self.next == 0 || self.is_done() || !self.pieces.iter().any(|p| p.2) {
+            return;
+        }
+        log::debug!(
+            "Flush {} pieces of a dropped stream transfer",
+            self.pieces.len() - self.next
+        );
+        while !self.is_done() {
+            if let Err(e) = self.write_next() {
+                log::error!("Failed to flush a dropped stream transfer: {}", e);
+                return;
+            }
+        }
+    }
+}
+
+/// A future which reads memory segments by DMA, at most [`ASYNC_DMA_PIECE_SIZE`] bytes per poll.
+///
+/// Dropping the future leaves the rest of the buffers unread, the device is not affected.
+pub struct ReadSegments<'a> {
+    client: &'a DriverClient,
+    segments: Vec<(u64, &'a mut [u8])>,
+}
+
+impl<'a> ReadSegments<'a> {
+    pub fn new(client: &'a DriverClient, segments: Vec<(u64, &'a mut [u8])>) -> Self {
+        let mut segments: Vec<(u64, &'a mut [u8])> = segments
+            .into_iter()
+            .flat_map(|(addr, data)| {
+                data.chunks_mut(ASYNC_DMA_PIECE_SIZE)
+                    .enumerate()
+                    .map(move |(i, d)| (addr + (i * ASYNC_DMA_PIECE_SIZE) as u64, d))
+            })
+            .collect();
+        // Pieces are popped from the end.
+        segments.reverse();
+        ReadSegments { client, segments }
+    }
+}
+
+impl Future for ReadSegments<'_> {
+    type Output = Result<()>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let (addr, data) = match self.segments.pop() {
+            Some(segment) => segment,
+            None => return Poll::Ready(Ok(())),
+        };
+        if let Err(e) = self.client.dma_read(addr, 0u64, data) {
+            return Poll::Ready(Err(e));
+        }
+        if self.segments.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use std::task::Wake;
+
+    /// A waker which reports wake-ups through a channel.
+    struct ChannelWaker(Mutex<Sender<()>>);
+
+    impl Wake for ChannelWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.lock().unwrap().send(()).unwrap();
+        }
+    }
+
+    #[test]
+    fn poll_device_yields_until_ready() {
+        let (tx, rx) = mpsc::channel();
+        let waker = Waker::from(Arc::new(ChannelWaker(Mutex::new(tx))));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut polls = 0;
+        let mut fut = poll_device(|| {
+            polls += 1;
+            Ok((polls == 3).then_some(polls))
+        });
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        // The future is woken by the timer thread, not right away by the poll.
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        assert_eq!(fut.interval, POLL_MIN_INTERVAL * 4);
+        rx.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(
+            Pin::new(&mut fut).poll(&mut cx),
+            Poll::Ready(Ok(3))
+        ));
+
+        let mut fut = poll_device::<(), _>(|| Err(DriverClientError::InvalidPrimitiveParam));
+        assert!(matches!(
+            Pin::new(&mut fut).poll(&mut cx),
+            Poll::Ready(Err(DriverClientError::InvalidPrimitiveParam))
+        ));
+    }
+}
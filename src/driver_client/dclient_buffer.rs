@@ -0,0 +1,316 @@
+//! Host buffers for DMA transfers.
+//!
+//! The XDMA driver pins user pages for every transfer. Page-aligned (optionally hugepage backed
+//! and `mlock`ed) buffers reduce the number of pages to pin and keep them resident,
+//! and the [`DmaBufferPool`] allows to reuse multi-gigabyte buffers between tasks.
+use crate::error::*;
+use std::{
+    io,
+    ops::{Deref, DerefMut},
+    ptr::{self, NonNull},
+    slice,
+    sync::Mutex,
+};
+
+const PAGE_SIZE: usize = 0x1000;
+const HUGE_PAGE_SIZE: usize = 0x200000;
+
+/// Allocation options of [`DmaBuffer`].
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct DmaBufferConfig {
+    /// Back the buffer by 2MB huge pages. If the system has no free huge pages,
+    /// the buffer is allocated with regular pages and advised to use transparent huge pages.
+    pub huge_pages: bool,
+    /// Lock the buffer in RAM with `mlock` so it is never swapped out.
+    pub lock: bool,
+}
+
+/// A page-aligned host buffer for DMA transfers.
+///
+/// The buffer dereferences to `[u8]` of its current length,
+/// so it can be passed directly anywhere a byte slice is expected.
+pub struct DmaBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    capacity: usize,
+    locked: bool,
+}
+
+// The buffer exclusively owns its memory.
+unsafe impl Send for DmaBuffer {}
+unsafe impl Sync for DmaBuffer {}
+
+impl DmaBuffer {
+    /// Allocates a zero-initialized buffer of `len` bytes.
+    pub fn new(len: usize, cfg: DmaBufferConfig) -> Result<Self> {
+        let page = if cfg.huge_pages {
+            HUGE_PAGE_SIZE
+        } else {
+            PAGE_SIZE
+        };
+        let capacity = len.max(1).div_ceil(page) * page;
+
+        let mut addr = libc::MAP_FAILED;
+        if cfg.huge_pages {
+            addr = mmap_anonymous(capacity, libc::MAP_HUGETLB);
+            if addr == libc::MAP_FAILED {
+                log::debug!("No free huge pages, fallback to transparent huge pages");
+            }
+        }
+        if addr == libc::MAP_FAILED {
+            addr = mmap_anonymous(capacity, 0);
+            if addr == libc::MAP_FAILED {
+                return Err(DriverClientError::MmapError {
+                    size: capacity,
+                    source: io::Error::last_os_error(),
+                });
+            }
+            if cfg.huge_pages {
+                unsafe { libc::madvise(addr, capacity, libc::MADV_HUGEPAGE) };
+            }
+        }
+
+        let mut buffer = DmaBuffer {
+            ptr: NonNull::new(addr as *mut u8).unwrap(),
+            len,
+            capacity,
+            locked: false,
+        };
+        if cfg.lock {
+            if unsafe { libc::mlock(addr, capacity) } != 0 {
+                return Err(DriverClientError::MlockError {
+                    size: capacity,
+                    source: io::Error::last_os_error(),
+                });
+            }
+            buffer.locked = true;
+        }
+        log::debug!("Allocate DMA buffer of size: {}", capacity);
+        Ok(buffer)
+    }
+
+    /// Allocates a buffer and copies `data` into it.
+    pub fn from_slice(data: &[u8], cfg: DmaBufferConfig) -> Result<Self> {
+        let mut buffer = Self::new(data.len(), cfg)?;
+        buffer.copy_from_slice(data);
+        Ok(buffer)
+    }
+
+    /// The number of bytes the buffer can hold without reallocation.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Is the buffer locked in RAM.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Changes the length of the buffer within its capacity.
+    /// The content of the buffer is preserved, new bytes are not initialized to any specific value.
+    pub fn set_len(&mut self, len: usize) -> Result<()> {
+        if len > self.capacity {
+            return Err(DriverClientError::BufferTooSmall {
+                capacity: self.capacity,
+                len,
+            });
+        }
+        self.len = len;
+        Ok(())
+    }
+}
+
+fn mmap_anonymous(size: usize, flags: libc::c_int) -> *mut libc::c_void {
+    unsafe {
+        libc::mmap(
+            ptr::null_mut(),
+            size,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | flags,
+            -1,
+            0,
+        )
+    }
+}
+
+impl Deref for DmaBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for DmaBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl AsRef<[u8]> for DmaBuffer {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl AsMut<[u8]> for DmaBuffer {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        let addr = self.ptr.as_ptr() as *mut libc::c_void;
+        unsafe {
+            if self.locked {
+                libc::munlock(addr, self.capacity);
+            }
+            libc::munmap(addr, self.capacity);
+        }
+    }
+}
+
+/// A pool of [`DmaBuffer`]s which are reused between tasks instead of being reallocated.
+///
+/// Buffers are taken from the pool with [`DmaBufferPool::acquire`] and returned back
+/// when the [`PooledDmaBuffer`] guard is dropped.
+pub struct DmaBufferPool {
+    cfg: DmaBufferConfig,
+    free: Mutex<Vec<DmaBuffer>>,
+}
+
+impl DmaBufferPool {
+    /// Creates an empty pool, new buffers are allocated with given options.
+    pub fn new(cfg: DmaBufferConfig) -> Self {
+        DmaBufferPool {
+            cfg,
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Takes the smallest free buffer which is able to hold `len` bytes,
+    /// or allocates a new one if there is no such buffer.
+    /// The content of a reused buffer is not cleared.
+    pub fn acquire(&self, len: usize) -> Result<PooledDmaBuffer<'_>> {
+        let reused = {
+            let mut free = self.free.lock().unwrap();
+            free.iter()
+                .enumerate()
+                .filter(|(_, b)| b.capacity() >= len)
+                .min_by_key(|(_, b)| b.capacity())
+                .map(|(i, _)| i)
+                .map(|i| free.swap_remove(i))
+        };
+        let mut buffer = match reused {
+            Some(buffer) => buffer,
+            None => DmaBuffer::new(len, self.cfg)?,
+        };
+        buffer.set_len(len)?;
+        Ok(PooledDmaBuffer {
+            pool: self,
+            buffer: Some(buffer),
+        })
+    }
+
+    /// Total capacity in bytes of buffers kept in the pool.
+    pub fn cached_size(&self) -> usize {
+        self.free.lock().unwrap().iter().map(|b| b.capacity()).sum()
+    }
+
+    /// Releases all free buffers kept in the pool.
+    pub fn clear(&self) {
+        self.free.lock().unwrap().clear();
+    }
+}
+
+impl Default for DmaBufferPool {
+    fn default() -> Self {
+        Self::new(DmaBufferConfig::default())
+    }
+}
+
+/// A [`DmaBuffer`] borrowed from a [`DmaBufferPool`], it goes back to the pool on drop.
+pub struct PooledDmaBuffer<'a> {
+    pool: &'a DmaBufferPool,
+    buffer: Option<DmaBuffer>,
+}
+
+impl PooledDmaBuffer<'_> {
+    /// Takes the buffer out of the pool permanently.
+    pub fn detach(mut self) -> DmaBuffer {
+        self.buffer.take().unwrap()
+    }
+}
+
+impl Deref for PooledDmaBuffer<'_> {
+    type Target = DmaBuffer;
+
+    fn deref(&self) -> &DmaBuffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledDmaBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut DmaBuffer {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl AsRef<[u8]> for PooledDmaBuffer<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl AsMut<[u8]> for PooledDmaBuffer<'_> {
+    fn as_mut(&mut self) -> &mut [u8] {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledDmaBuffer<'_> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.free.lock().unwrap().push(buffer);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffer_is_page_aligned() {
+        let buffer = DmaBuffer::new(100, DmaBufferConfig::default()).unwrap();
+        assert_eq!(buffer.as_ptr() as usize % PAGE_SIZE, 0);
+        assert_eq!(buffer.len(), 100);
+        assert_eq!(buffer.capacity(), PAGE_SIZE);
+        assert!(buffer.iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn pool_reuses_buffers() {
+        let pool = DmaBufferPool::default();
+        let ptr = {
+            let mut buffer = pool.acquire(3 * PAGE_SIZE).unwrap();
+            buffer[0] = 1;
+            buffer.as_ptr()
+        };
+        assert_eq!(pool.cached_size(), 3 * PAGE_SIZE);
+
+        let small = pool.acquire(PAGE_SIZE).unwrap();
+        assert_eq!(small.as_ptr(), ptr);
+        assert_eq!(small.len(), PAGE_SIZE);
+        assert_eq!(pool.cached_size(), 0);
+
+        let other = pool.acquire(PAGE_SIZE).unwrap();
+        assert_ne!(other.as_ptr(), ptr);
+        drop(small);
+        drop(other);
+        assert_eq!(pool.cached_size(), 4 * PAGE_SIZE);
+        pool.clear();
+        assert_eq!(pool.cached_size(), 0);
+    }
+}
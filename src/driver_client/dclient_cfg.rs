@@ -1,3 +1,5 @@
+use super::dclient_buffer::DmaBufferConfig;
+
 pub enum CardType {
     C1100,
 }
@@ -16,10 +18,20 @@ pub struct DriverConfig {
     pub(crate) ctrl_firewall_baseaddr: u64,
     pub(crate) dma_firewall_baseaddr: u64,
     pub(crate) ctrl_dfx_decoupler_baseaddr: u64,
+    /// Size of the AXI-Lite user BAR which is memory-mapped for register access.
+    pub(crate) ctrl_bar_size: u64,
 
     // DMA
     pub(crate) dma_baseaddr: u64,
     pub(crate) dma_hbicap_baseaddr: u64,
+    /// Number of XDMA channels to open in each direction.
+    pub(crate) dma_channels: usize,
+    /// Minimal size of a memory transfer in bytes that is striped across DMA channels.
+    pub(crate) dma_stripe_min_size: usize,
+    /// Allocation options of host buffers kept in the DMA buffer pool.
+    pub(crate) dma_buffer_cfg: DmaBufferConfig,
+    /// Size of HBM of the card in bytes, HBM starts at address 0 of the DMA space.
+    pub(crate) hbm_size: u64,
 }
 
 impl DriverConfig {
@@ -29,8 +41,42 @@ impl DriverConfig {
             CardType::C1100 => c1100_cfg(),
         }
     }
+
+    /// Sets the number of XDMA channels opened in each direction, from 1 up to 4.
+    pub fn with_dma_channels(mut self, nof_channels: usize) -> Self {
+        self.dma_channels = nof_channels.clamp(1, XDMA_MAX_CHANNELS);
+        self
+    }
+
+    /// Sets the minimal size of a transfer in bytes that is striped across DMA channels
+    /// by [`DriverClient::dma_write_striped`](super::DriverClient::dma_write_striped)
+    /// and [`DriverClient::dma_read_striped`](super::DriverClient::dma_read_striped).
+    pub fn with_dma_stripe_min_size(mut self, size: usize) -> Self {
+        self.dma_stripe_min_size = size;
+        self
+    }
+
+    /// Sets allocation options of host buffers kept in the DMA buffer pool.
+    pub fn with_dma_buffers(mut self, buffer_cfg: DmaBufferConfig) -> Self {
+        self.dma_buffer_cfg = buffer_cfg;
+        self
+    }
+
+    /// Sets the size of HBM in bytes, e.g. if only a part of it is given to the primitive.
+    pub fn with_hbm_size(mut self, size: u64) -> Self {
+        self.hbm_size = size;
+        self
+    }
+
+    /// Size of HBM of the card in bytes.
+    pub fn hbm_size(&self) -> u64 {
+        self.hbm_size
+    }
 }
 
+/// XDMA exposes up to four channels per direction.
+const XDMA_MAX_CHANNELS: usize = 4;
+
 fn c1100_cfg() -> DriverConfig {
     DriverConfig {
         ctrl_baseaddr: 0x00000000,
@@ -41,7 +87,15 @@ fn c1100_cfg() -> DriverConfig {
         ctrl_firewall_baseaddr: 0x04070000,
         dma_firewall_baseaddr: 0x04080000,
         ctrl_dfx_decoupler_baseaddr: 0x04090000,
+        ctrl_bar_size: 0x08000000,
         dma_baseaddr: 0x0000000000000000,
         dma_hbicap_baseaddr: 0x1000000000000000,
+        dma_channels: 4,
+        dma_stripe_min_size: 0x4000000,
+        dma_buffer_cfg: DmaBufferConfig {
+            huge_pages: true,
+            lock: false,
+        },
+        hbm_size: 0x400000000,
     }
 }
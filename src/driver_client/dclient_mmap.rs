@@ -0,0 +1,141 @@
+//! Memory-mapped access to the AXI-Lite register space of the user BAR.
+//!
+//! Every register access through the character device costs a `pread`/`pwrite` syscall.
+//! Mapping the BAR into the process turns each access into a single volatile load or store,
+//! which matters for polling loops and for reading results out register by register.
+use crate::error::*;
+use std::{fs::File, io, os::unix::io::AsRawFd, ptr};
+
+/// A mapping of the AXI-Lite register space exposed by `/dev/xdma{id}_user`.
+///
+/// All accesses are volatile and 32-bit wide since AXI-Lite only supports 32-bit transactions.
+/// 64-bit registers are accessed as a `LO`/`HI` pair of 32-bit transactions.
+pub struct RegisterMap {
+    base: *mut u8,
+    size: usize,
+}
+
+// The mapping points to device memory that is valid for the whole lifetime of the struct.
+// Volatile 32-bit accesses to AXI-Lite registers are atomic from the bus point of view.
+unsafe impl Send for RegisterMap {}
+unsafe impl Sync for RegisterMap {}
+
+impl RegisterMap {
+    /// Maps `size` bytes of the register space from the beginning of the BAR.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctrl`: an opened read/write file descriptor of the user BAR.
+    /// * `size`: the size of the BAR in bytes.
+    pub fn new(ctrl: &File, size: usize) -> Result<Self> {
+        let base = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                ctrl.as_raw_fd(),
+                0,
+            )
+        };
+        if base == libc::MAP_FAILED {
+            return Err(DriverClientError::MmapError {
+                size,
+                source: io::Error::last_os_error(),
+            });
+        }
+        Ok(RegisterMap {
+            base: base as *mut u8,
+            size,
+        })
+    }
+
+    /// The size of the mapped register space in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Checks that an access of `len` bytes at `addr` lies inside the mapping
+    /// and is made of whole 32-bit registers.
+    pub fn contains(&self, addr: u64, len: usize) -> bool {
+        addr & 0x3 == 0
+            && len & 0x3 == 0
+            && (addr as usize)
+                .checked_add(len)
+                .is_some_and(|end| end <= self.size)
+    }
+
+    fn check(&self, addr: u64, len: usize) -> Result<()> {
+        if self.contains(addr, len) {
+            Ok(())
+        } else {
+            Err(DriverClientError::RegisterOutOfRange {
+                addr,
+                size: self.size,
+            })
+        }
+    }
+
+    /// Volatile read of a 32-bit register at the given byte address.
+    pub fn read_u32(&self, addr: u64) -> Result<u32> {
+        self.check(addr, 4)?;
+        let val = unsafe { ptr::read_volatile(self.base.add(addr as usize) as *const u32) };
+        Ok(u32::from_le(val))
+    }
+
+    /// Volatile write of a 32-bit register at the given byte address.
+    pub fn write_u32(&self, addr: u64, data: u32) -> Result<()> {
+        self.check(addr, 4)?;
+        unsafe { ptr::write_volatile(self.base.add(addr as usize) as *mut u32, data.to_le()) };
+        Ok(())
+    }
+
+    /// Reads a 64-bit value stored as a `LO`/`HI` pair of 32-bit registers starting at `addr`.
+    /// The `LO` register is read first.
+    pub fn read_u64(&self, addr: u64) -> Result<u64> {
+        self.check(addr, 8)?;
+        let lo = self.read_u32(addr)? as u64;
+        let hi = self.read_u32(addr + 4)? as u64;
+        Ok(hi << 32 | lo)
+    }
+
+    /// Writes a 64-bit value as a `LO`/`HI` pair of 32-bit registers starting at `addr`.
+    /// The `LO` register is written first.
+    pub fn write_u64(&self, addr: u64, data: u64) -> Result<()> {
+        self.check(addr, 8)?;
+        self.write_u32(addr, data as u32)?;
+        self.write_u32(addr + 4, (data >> 32) as u32)
+    }
+
+    /// Reads consecutive 32-bit registers starting at `addr` into `buf`.
+    /// The length of `buf` must be a multiple of 4.
+    pub fn read_into(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        self.check(addr, buf.len())?;
+        buf.chunks_exact_mut(4)
+            .enumerate()
+            .try_for_each(|(i, chunk)| {
+                chunk.copy_from_slice(&self.read_u32(addr + (i * 4) as u64)?.to_le_bytes());
+                Ok(())
+            })
+    }
+
+    /// Writes `data` into consecutive 32-bit registers starting at `addr`.
+    /// The length of `data` must be a multiple of 4.
+    pub fn write_from(&self, addr: u64, data: &[u8]) -> Result<()> {
+        self.check(addr, data.len())?;
+        data.chunks_exact(4).enumerate().try_for_each(|(i, chunk)| {
+            self.write_u32(
+                addr + (i * 4) as u64,
+                u32::from_le_bytes(chunk.try_into().unwrap()),
+            )
+        })
+    }
+}
+
+impl Drop for RegisterMap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.base as *mut libc::c_void, self.size);
+        }
+    }
+}
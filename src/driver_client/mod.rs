@@ -1,7 +1,15 @@
 mod dclient;
+#[cfg(feature = "async")]
+mod dclient_async;
+mod dclient_buffer;
 mod dclient_cfg;
 mod dclient_code;
+mod dclient_mmap;
 
 pub use dclient::*;
+#[cfg(feature = "async")]
+pub use dclient_async::*;
+pub use dclient_buffer::{DmaBuffer, DmaBufferConfig, DmaBufferPool, PooledDmaBuffer};
 pub use dclient_cfg::{CardType, DriverConfig};
 pub(crate) use dclient_code::*;
+pub use dclient_mmap::RegisterMap;
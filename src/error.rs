@@ -1,4 +1,4 @@
-use std::io;
+use std::{io, ops::Range, time::Duration};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, DriverClientError>;
@@ -27,6 +27,138 @@ pub enum DriverClientError {
     LoadFailed { path: String },
     #[error("failed open file")]
     FileError(#[from] io::Error),
+    #[error("failed to map memory of size {:#X?}", size)]
+    MmapError {
+        size: usize,
+        #[source]
+        source: io::Error,
+    },
+    #[error("failed to lock buffer of size {:#X?} in memory", size)]
+    MlockError {
+        size: usize,
+        #[source]
+        source: io::Error,
+    },
+    #[error("buffer of capacity {} can't hold {} bytes", capacity, len)]
+    BufferTooSmall { capacity: usize, len: usize },
+    #[error(
+        "register address {:#X?} is out of mapped space of size {:#X?}",
+        addr,
+        size
+    )]
+    RegisterOutOfRange { addr: u64, size: usize },
+    #[error("input of size {} doesn't match expected size {}", actual, expected)]
+    InputSizeMismatch { expected: usize, actual: usize },
+    #[error(
+        "number of bases {} doesn't match number of scalars {}",
+        bases,
+        scalars
+    )]
+    LengthMismatch { bases: usize, scalars: usize },
+    #[error(
+        "client is created for {} curve, but {} points are given",
+        client,
+        requested
+    )]
+    CurveMismatch { client: String, requested: String },
+    #[error(
+        "client is created for {} points, but {} points are given",
+        client,
+        requested
+    )]
+    GroupMismatch { client: String, requested: String },
+    #[error("{} results can't be read from the MSM core", group)]
+    UnsupportedGroup { group: String },
+    #[error("malformed MSM result: {}", reason)]
+    MalformedResult { reason: String },
+    #[error("scalar {} is not below the scalar field modulus", index)]
+    ScalarOutOfRange { index: usize },
+    #[error("MSM input has no points")]
+    MissingPoints,
+    #[error("base {} is not a point of the curve", index)]
+    InvalidBase { index: usize },
+    #[error("compressed point {} is not a valid point of the curve", index)]
+    InvalidCompressedPoint { index: usize },
+    #[error("{} compression is not supported for {} curve", compression, curve)]
+    UnsupportedCompression { curve: String, compression: String },
+    #[error("precompute factor {} doesn't divide the scalar size", factor)]
+    InvalidPrecomputeFactor { factor: u32 },
+    #[error("invalid precomputed bases cache: {}", reason)]
+    InvalidPrecomputeCache { reason: String },
+    #[error(
+        "MSM of {} elements exceeds the task size {}",
+        nof_elements,
+        max_nof_elements
+    )]
+    TaskTooLarge {
+        nof_elements: usize,
+        max_nof_elements: usize,
+    },
+    #[error("result label {} doesn't belong to any submitted task", label)]
+    ResultLabelMismatch { label: u32 },
+    #[error("result of task with label {} is missing", label)]
+    MissingResult { label: u32 },
+    #[error("base sets need an MSM client with points in HBM")]
+    HbmNotEnabled,
+    #[error("base set {:?} is already loaded", name)]
+    BaseSetExists { name: String },
+    #[error("base set {:?} is not loaded", name)]
+    UnknownBaseSet { name: String },
+    #[error("base set {:?} is used by a running MSM", name)]
+    BaseSetInUse { name: String },
+    #[error("HBM region {:#X?}..{:#X?} overlaps base set {:?}", start, end, name)]
+    HbmRegionOverlap { name: String, start: u64, end: u64 },
+    #[error(
+        "no free HBM region of {:#X?} bytes, HBM capacity is {:#X?}",
+        size,
+        capacity
+    )]
+    HbmOutOfMemory { size: u64, capacity: u64 },
+    #[error(
+        "range {}..{} is out of base set {:?} of {} elements",
+        start,
+        end,
+        name,
+        nof_elements
+    )]
+    InvalidBaseSetRange {
+        name: String,
+        start: usize,
+        end: usize,
+        nof_elements: usize,
+    },
+    #[error("data in HBM doesn't match uploaded data in {:X?}", ranges)]
+    HbmDataMismatch { ranges: Vec<Range<u64>> },
+    #[error("invalid MSM tuning: {}", reason)]
+    InvalidTuning { reason: String },
+    #[error(
+        "MSM core is stalled with {} elements left in the task for {:?}",
+        elements_left,
+        stalled_for
+    )]
+    CoreStalled {
+        elements_left: u32,
+        stalled_for: Duration,
+    },
+    #[error(
+        "loaded MSM image doesn't compute {} MSM over {} curve, image parameters {:#X}",
+        group,
+        curve,
+        image_params
+    )]
+    ImageMismatch {
+        curve: String,
+        group: String,
+        image_params: u32,
+    },
+    #[error(
+        "client expects precompute factor {}, but the loaded image is built for {}",
+        client,
+        image
+    )]
+    PrecomputeFactorMismatch { client: u32, image: u32 },
+    #[error("MSM result check failed for input {:#X}", fingerprint)]
+    MSMCheckFailed { fingerprint: u64 },
     #[error("unknown driver client error")]
     Unknown,
 }
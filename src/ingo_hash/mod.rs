@@ -1,5 +1,7 @@
 mod hash_hw_code;
 mod poseidon_api;
+#[cfg(feature = "async")]
+mod poseidon_async;
 mod utils;
 
 pub use poseidon_api::*;
@@ -14,6 +14,10 @@ pub enum Hash {
 
 pub struct PoseidonClient {
     pub dclient: DriverClient,
+    /// Results read from the device by `result_async` which are not returned yet,
+    /// both `result` and `result_async` return them first.
+    #[cfg(feature = "async")]
+    pub(super) received: std::sync::Mutex<Vec<PoseidonResult>>,
 }
 
 #[derive(Clone)]
@@ -75,7 +79,11 @@ impl DriverPrimitive<Hash, PoseidonInitializeParameters, &[u8], Vec<PoseidonResu
     for PoseidonClient
 {
     fn new(_ptype: Hash, dclient: DriverClient) -> Self {
-        PoseidonClient { dclient }
+        PoseidonClient {
+            dclient,
+            #[cfg(feature = "async")]
+            received: Default::default(),
+        }
     }
 
     fn loaded_binary_parameters(&self) -> Vec<u32> {
@@ -126,6 +134,10 @@ impl DriverPrimitive<Hash, PoseidonInitializeParameters, &[u8], Vec<PoseidonResu
     }
 
     fn result(&self, expected_result: Option<usize>) -> Result<Option<Vec<PoseidonResult>>> {
+        // Results kept by `result_async` come first, they were read from the device earlier.
+        #[cfg(feature = "async")]
+        let mut results: Vec<PoseidonResult> = self.received.lock().unwrap().drain(..).collect();
+        #[cfg(not(feature = "async"))]
         let mut results: Vec<PoseidonResult> = vec![];
 
         loop {
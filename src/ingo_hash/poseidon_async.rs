@@ -0,0 +1,55 @@
+//! Async versions of the data transfer and result methods of [`PoseidonClient`].
+//!
+//! Results are kept by the client as soon as they are read from the device,
+//! so a dropped [`PoseidonClient::result_async`] doesn't lose them.
+use super::poseidon_api::*;
+use crate::{driver_client::*, error::*};
+
+impl PoseidonClient {
+    /// Async version of [`DriverPrimitive::set_data`].
+    ///
+    /// The input is streamed piece by piece between polls.
+    /// If the future is dropped after the transfer is started, the rest of the input is written synchronously.
+    pub async fn set_data_async(&self, input: &[u8]) -> Result<()> {
+        let addr = self.dclient.cfg.dma_baseaddr + u64::from(DMA_RW::OFFSET);
+        WriteSegments::new(&self.dclient)
+            .stream(&[(addr, input)])
+            .await
+    }
+
+    /// Waits until at least one result is pending on the device.
+    pub async fn wait_result_async(&self) -> Result<()> {
+        poll_device(|| {
+            let ready =
+                !self.received.lock().unwrap().is_empty() || self.get_num_of_pending_results()? > 0;
+            Ok(ready.then_some(()))
+        })
+        .await
+    }
+
+    /// Async version of [`DriverPrimitive::result`], returns the next `expected_result` results.
+    ///
+    /// Pending results are read from the device once per poll. Results beyond `expected_result`
+    /// are kept for the next call, as well as all results read by a dropped future.
+    pub async fn result_async(
+        &self,
+        expected_result: usize,
+    ) -> Result<Option<Vec<PoseidonResult>>> {
+        poll_device(|| {
+            let mut received = self.received.lock().unwrap();
+            if received.len() < expected_result {
+                let num_of_pending_results = self.get_num_of_pending_results()?;
+                if num_of_pending_results > 0 {
+                    let res = self.get_raw_results(num_of_pending_results)?;
+                    received.append(&mut PoseidonResult::parse_poseidon_hash_results(res));
+                }
+            }
+            if received.len() < expected_result {
+                return Ok(None);
+            }
+            Ok(Some(received.drain(..expected_result).collect()))
+        })
+        .await
+        .map(Some)
+    }
+}
@@ -1,6 +1,34 @@
 mod msm_api;
+#[cfg(feature = "async")]
+mod msm_async;
+mod msm_backend;
 mod msm_cfg;
+mod msm_check;
+mod msm_compressed;
+mod msm_cpu;
+mod msm_curve;
+mod msm_filter;
+mod msm_hbm;
 mod msm_hw_code;
+mod msm_precompute;
+mod msm_scalars;
+mod msm_stats;
+mod msm_stream;
+mod msm_task;
+mod msm_verify;
 
 pub use msm_api::*;
-pub use msm_cfg::{Curve, PointMemoryType};
+pub use msm_backend::MSMBackend;
+pub use msm_cfg::{Curve, MSMGroup, PointMemoryType, MAX_NOF_ELEMENTS};
+pub use msm_check::{CheckedMSM, MSMCheckConfig, MSMCheckMethod, MSMCheckStats, MSMMismatch};
+pub use msm_compressed::{decompress_bases, decompress_file, decompress_points, PointCompression};
+pub use msm_cpu::{CpuMSM, HostBases};
+pub use msm_curve::{MSMCurve, MSMCurveParameters, MSMField};
+pub use msm_filter::{filter_scalars, FilteredMSM, MSMFilterStats};
+pub use msm_hbm::{HbmAllocator, HbmBaseSet, HBM_REGION_ALIGNMENT};
+pub use msm_precompute::*;
+pub use msm_scalars::{ScalarEncoding, Scalars, ScalarsBuilder};
+pub use msm_stats::{MSMFifoCounters, MSMPhaseCounters, MSMTaskStats, DEFAULT_CORE_CLOCK_HZ};
+pub use msm_stream::{MSMStreamConfig, MSMStreamProgress};
+pub use msm_task::MsmTaskHandle;
+pub use msm_verify::{HbmMismatch, HbmVerify, HBM_VERIFY_BLOCK_SIZE};
@@ -1,33 +1,115 @@
-use super::{msm_cfg::*, msm_hw_code::*};
-use crate::{driver_client::*, error::*};
+use super::{
+    msm_cfg::*, msm_curve::combine_results, msm_hbm::HbmAllocator, msm_hw_code::*,
+    msm_precompute::check_precompute_factor, msm_stats::DEFAULT_CORE_CLOCK_HZ, msm_task::TaskQueue,
+    msm_verify::HbmVerify,
+};
+use crate::{driver_client::*, error::*, utils::MappedFile};
 
 use packed_struct::prelude::*;
-use std::os::unix::fs::FileExt;
+use std::{
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
 use strum::IntoEnumIterator;
 
 pub struct MSMClient {
-    mem_type: PointMemoryType,
+    pub(super) mem_type: PointMemoryType,
+    pub(super) curve: Curve,
+    pub(super) group: MSMGroup,
     // If precompute factor set to 1 is the basic MSM computation without optimization
-    precompute_factor: u32,
-    msm_cfg: MSMConfig,
+    pub(super) precompute_factor: u32,
+    pub(super) msm_cfg: MSMConfig,
+    pub(super) tuning: MSMTuning,
+    pub(super) tasks: TaskQueue,
+    pub(super) hbm: Mutex<HbmAllocator>,
+    pub(super) hbm_verify: HbmVerify,
+    pub(super) core_clock_hz: u64,
+    /// Whether the precompute factor was checked against the loaded image by the first task.
+    pub(super) precompute_checked: AtomicBool,
     pub driver_client: DriverClient,
 }
 
+#[derive(Debug, Copy, Clone)]
 pub struct MSMInit {
     pub mem_type: PointMemoryType,
-    pub is_precompute: bool,
+    /// Every base is followed by `precompute_factor - 1` precomputed multiples,
+    /// [`PRECOMPUTE_FACTOR_BASE`] for images without precomputation. The factor must divide
+    /// the scalar bit size and match the loaded image, see [`MSMClient::check_image`].
+    pub precompute_factor: u32,
     pub curve: Curve,
+    /// The group of bases, the loaded image must be built for it, see [`MSMClient::check_image`].
+    pub group: MSMGroup,
+    /// Tuning of the core used by all tasks unless a task overrides it.
+    pub tuning: MSMTuning,
 }
 
 #[derive(Debug, Copy, Clone)]
 pub struct MSMParams {
     pub nof_elements: u32,
     pub hbm_point_addr: Option<(u64, u64)>,
+    /// Overrides the tuning of the client for the task.
+    pub tuning: Option<MSMTuning>,
+}
+
+/// Where the MSM core takes scalars from.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CoefficientsSource {
+    /// Scalars are streamed by DMA together with bases.
+    DMA,
+    /// Scalars are written into HBM at `addr` and read from there by the core.
+    /// The region must not overlap resident base sets, otherwise setting data fails
+    /// with [`DriverClientError::HbmRegionOverlap`].
+    HBM { addr: u64 },
+}
+
+/// Tuning knobs of the MSM core.
+///
+/// The tuning is checked when a task is initialized: the chunk size must keep DMA transfers
+/// aligned and the optimizations register must keep every bit of `optimizations` when it is
+/// read back, otherwise [`DriverClientError::InvalidTuning`] is returned.
+/// [`MSMImageParametrs`] don't describe optimizations or the source of scalars, so the tuning
+/// is not checked against them, and an image which ignores the coefficients source register
+/// is not detected.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MSMTuning {
+    /// Value of the optimizations register, each bit enables an optimization of the image.
+    pub optimizations: u32,
+    /// Number of elements sent to the core by one DMA transfer.
+    pub dma_chunk_size: usize,
+    pub coefficients_source: CoefficientsSource,
+}
+
+impl Default for MSMTuning {
+    fn default() -> Self {
+        MSMTuning {
+            optimizations: 0,
+            dma_chunk_size: DEFAULT_DMA_CHUNK_SIZE,
+            coefficients_source: CoefficientsSource::DMA,
+        }
+    }
+}
+
+impl MSMTuning {
+    /// Moves scalars in HBM by `offset` bytes, e.g. for a part of a larger task.
+    pub(super) fn offset_coefficients(mut self, offset: u64) -> Self {
+        if let CoefficientsSource::HBM { addr } = self.coefficients_source {
+            self.coefficients_source = CoefficientsSource::HBM {
+                addr: addr + offset,
+            };
+        }
+        self
+    }
 }
 
-pub struct MSMInput {
-    pub points: Option<Vec<u8>>,
-    pub scalars: Vec<u8>,
+/// Input of one MSM task. Points and scalars are borrowed,
+/// so they can live in a `Vec<u8>`, a [`DmaBuffer`] or any other memory.
+#[derive(Debug, Copy, Clone)]
+pub struct MSMInput<'a> {
+    pub points: Option<&'a [u8]>,
+    pub scalars: &'a [u8],
     pub params: MSMParams,
 }
 #[derive(Debug, Clone)]
@@ -39,19 +121,30 @@ pub struct MSMResult {
 pub const PRECOMPUTE_FACTOR_BASE: u32 = 1;
 pub const PRECOMPUTE_FACTOR: u32 = 8;
 
-impl DriverPrimitive<MSMInit, MSMParams, MSMInput, MSMResult> for MSMClient {
+/// Number of elements sent to the core by one DMA transfer by default.
+pub const DEFAULT_DMA_CHUNK_SIZE: usize = 2048;
+/// DMA transfers of a chunk of scalars must be a whole number of stream beats of this size.
+const DMA_BEAT_SIZE: usize = 64;
+
+/// Number of elements fed to the device at once when the input is streamed from files.
+/// It is a multiple of the default DMA chunk size, so chunks are the same as for a single [`MSMInput`].
+pub const FILE_WINDOW_ELEMENTS: usize = 1 << 20;
+/// Number of bytes loaded into HBM at once when points are streamed from a file.
+pub const FILE_WINDOW_BYTES: usize = 1 << 28;
+
+/// Size of the result registers, they end where [`INGO_MSM_ADDR::ADDR_CPU2HIF_E_POP_RESULT`] starts.
+/// A projective G1 point of every curve fits, a G2 point doesn't.
+const RESULT_WINDOW_SIZE: usize = INGO_MSM_ADDR::ADDR_CPU2HIF_E_POP_RESULT as usize
+    - INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT as usize;
+
+impl<'a> DriverPrimitive<MSMInit, MSMParams, MSMInput<'a>, MSMResult> for MSMClient {
     /// Creates a new [`MSMClient`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group or the precompute factor is not supported, see [`MSMClient::try_new`].
     fn new(init: MSMInit, dclient: DriverClient) -> Self {
-        MSMClient {
-            mem_type: init.mem_type,
-            precompute_factor: if init.is_precompute {
-                PRECOMPUTE_FACTOR
-            } else {
-                PRECOMPUTE_FACTOR_BASE
-            },
-            msm_cfg: MSMConfig::msm_cfg(init.curve, init.mem_type),
-            driver_client: dclient,
-        }
+        MSMClient::try_new(init, dclient).unwrap()
     }
 
     fn loaded_binary_parameters(&self) -> Vec<u32> {
@@ -70,7 +163,14 @@ impl DriverPrimitive<MSMInit, MSMParams, MSMInput, MSMResult> for MSMClient {
     }
 
     fn initialize(&self, params: MSMParams) -> Result<()> {
+        // The image is usually loaded after the client is created, so it is checked by the first task.
+        if !self.precompute_checked.load(Ordering::Relaxed) {
+            self.check_precompute_factor()?;
+            self.precompute_checked.store(true, Ordering::Relaxed);
+        }
         log::info!("Start initialize driver");
+        let tuning = self.task_tuning(&params);
+        self.set_optimizations(&tuning)?;
 
         if self.mem_type == PointMemoryType::DMA && params.hbm_point_addr.is_none() {
             log::info!("Setup DMA bases");
@@ -94,11 +194,28 @@ impl DriverPrimitive<MSMInit, MSMParams, MSMInput, MSMResult> for MSMClient {
             )?;
         }
 
-        self.driver_client.ctrl_write_u32(
-            self.driver_client.cfg.ctrl_baseaddr,
-            INGO_MSM_ADDR::ADDR_CPU2HIF_C_COEFFICIENTS_SOURCE,
-            0,
-        )?;
+        match tuning.coefficients_source {
+            CoefficientsSource::DMA => {
+                self.driver_client.ctrl_write_u32(
+                    self.driver_client.cfg.ctrl_baseaddr,
+                    INGO_MSM_ADDR::ADDR_CPU2HIF_C_COEFFICIENTS_SOURCE,
+                    0,
+                )?;
+            }
+            CoefficientsSource::HBM { addr } => {
+                log::info!("Setup HBM coefficients");
+                self.driver_client.ctrl_write_u32(
+                    self.driver_client.cfg.ctrl_baseaddr,
+                    INGO_MSM_ADDR::ADDR_CPU2HIF_C_COEFFICIENTS_SOURCE,
+                    1,
+                )?;
+                self.driver_client.ctrl_write(
+                    self.driver_client.cfg.ctrl_baseaddr,
+                    INGO_MSM_ADDR::ADDR_CPU2HIF_C_COEFFICIENTS_HBM_START_ADDRESS_LO,
+                    &addr.to_le_bytes(),
+                )?;
+            }
+        }
 
         log::info!("Set NOF Elements: {}", params.nof_elements);
         self.driver_client.ctrl_write_u32(
@@ -152,111 +269,34 @@ impl DriverPrimitive<MSMInit, MSMParams, MSMInput, MSMResult> for MSMClient {
     /// }
     /// ```
     ///
-    fn set_data(&self, data: MSMInput) -> Result<()> {
-        const CHUNK_SIZE: usize = 2048;
-        let chunks = (data.params.nof_elements as usize + (CHUNK_SIZE - 1)) / CHUNK_SIZE;
-
-        let payload_size_scalars = CHUNK_SIZE * self.msm_cfg.scalar_size;
-        // Scalar addres can be loaded from configuration file or setup by user in input parametrs
-        let s_addr = self.msm_cfg.dma_scalars_addr.unwrap();
-
-        if data.points.is_none() && data.params.hbm_point_addr.is_some() {
-            log::debug!("Set only scalars");
-            for i in 0..chunks {
-                let (s_start, s_end) = if i != chunks - 1 {
-                    (i * payload_size_scalars, (i + 1) * payload_size_scalars)
-                } else {
-                    (i * payload_size_scalars, data.scalars.len())
-                };
-                let s_chunk = &data.scalars[s_start..s_end];
-                self.driver_client
-                    .dma_write(s_addr, DMA_RW::OFFSET, s_chunk)?;
-            }
-        } else if data.points.is_some() && data.params.hbm_point_addr.is_none() {
-            log::debug!("Set points and scalars");
-            let mut payload_size_points = CHUNK_SIZE * self.msm_cfg.point_size.unwrap();
-            payload_size_points *= self.precompute_factor as usize;
-
-            let p_addr = self.msm_cfg.dma_points_addr.unwrap();
-            let p = data.points.as_ref().unwrap();
-
-            for i in 0..chunks {
-                let (p_start, p_end) = if i != chunks - 1 {
-                    (i * payload_size_points, (i + 1) * payload_size_points)
-                } else {
-                    (i * payload_size_points, p.len())
-                };
-                let p_chunk = &p[p_start..p_end];
-
-                let (s_start, s_end) = if i != chunks - 1 {
-                    (i * payload_size_scalars, (i + 1) * payload_size_scalars)
-                } else {
-                    (i * payload_size_scalars, data.scalars.len())
-                };
-                let s_chunk = &data.scalars[s_start..s_end];
-
-                self.driver_client
-                    .dma_write(s_addr, DMA_RW::OFFSET, s_chunk)?;
-                self.driver_client
-                    .dma_write(p_addr, DMA_RW::OFFSET, p_chunk)?;
-            }
-        } else if data.points.is_some() && data.params.hbm_point_addr.is_some() {
-            let hbm_addr = data.params.hbm_point_addr.unwrap();
-            self.load_data_to_hbm(data.points.as_ref().unwrap(), hbm_addr.0, hbm_addr.1)?;
-
-            for i in 0..chunks {
-                let (s_start, s_end) = if i != chunks - 1 {
-                    (i * payload_size_scalars, (i + 1) * payload_size_scalars)
-                } else {
-                    (i * payload_size_scalars, data.scalars.len())
-                };
-                let s_chunk = &data.scalars[s_start..s_end];
-                self.driver_client
-                    .dma_write(s_addr, DMA_RW::OFFSET, s_chunk)?;
-            }
+    fn set_data(&self, data: MSMInput<'a>) -> Result<()> {
+        if let (Some(p), Some((addr, offset))) = (data.points, data.params.hbm_point_addr) {
+            self.load_data_to_hbm(p, addr, offset)?;
         }
-
-        Ok(())
+        if let CoefficientsSource::HBM { addr } = self.task_tuning(&data.params).coefficients_source
+        {
+            log::debug!("Set scalars to HBM: {:#X?}", addr);
+            self.check_hbm_write(addr, data.scalars.len())?;
+            self.driver_client
+                .dma_write_striped(addr, 0u64, data.scalars)?;
+        }
+        self.driver_client
+            .dma_write_vectored(&self.stream_segments(&data))
     }
 
     fn wait_result(&self) -> Result<()> {
-        let mut result_valid = [0, 0, 0, 0];
-        while result_valid == [0, 0, 0, 0] {
-            self.driver_client
-                .ctrl
-                .read_exact_at(
-                    &mut result_valid,
-                    self.driver_client.cfg.ctrl_baseaddr
-                        + INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT_VALID as u64,
-                )
-                .map_err(|e| DriverClientError::ReadError {
-                    offset: "ADDR_HIF2CPU_C_RESULT_VALID".to_string(),
-                    source: e,
-                })?;
-        }
+        while !self.is_result_valid()? {}
         Ok(())
     }
 
     fn result(&self, _param: Option<usize>) -> Result<Option<MSMResult>> {
         log::info!("Received result...");
-        let mut result: Vec<u8> = Vec::new();
-        // Divide size into chunks of 4 bytes
-        for i in 0..(self.msm_cfg.result_point_size / 4) {
-            let mut read_chunk = [1, 1, 1, 1];
-            self.driver_client
-                .ctrl
-                .read_exact_at(
-                    &mut read_chunk,
-                    self.driver_client.cfg.ctrl_baseaddr
-                        + INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT as u64
-                        + (i * 4) as u64,
-                )
-                .map_err(|e| DriverClientError::ReadError {
-                    offset: format!("ADDR_HIF2CPU_C_RESULT in {:?} chunk", i * 4),
-                    source: e,
-                })?;
-            result.extend(read_chunk);
-        }
+        let mut result = vec![0; self.msm_cfg.result_point_size];
+        self.driver_client.ctrl_read(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT,
+            &mut result,
+        )?;
         let result_label = self.driver_client.ctrl_read_u32(
             self.driver_client.cfg.ctrl_baseaddr,
             INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT_LABEL,
@@ -275,6 +315,253 @@ impl DriverPrimitive<MSMInit, MSMParams, MSMInput, MSMResult> for MSMClient {
 }
 
 impl MSMClient {
+    /// Creates a new [`MSMClient`], returns [`DriverClientError::InvalidPrecomputeFactor`]
+    /// if the precompute factor doesn't divide the scalar bit size.
+    ///
+    /// G2 clients are rejected with [`DriverClientError::UnsupportedGroup`]: a G2 result doesn't fit
+    /// into the result registers of the core, and the result registers of G2 images are not known yet.
+    /// G2 MSMs can be computed by [`CpuMSM`](super::CpuMSM).
+    ///
+    /// The client is usually created before the image is loaded, so the precompute factor
+    /// is checked against the image by the first task, see [`MSMClient::check_image`].
+    pub fn try_new(init: MSMInit, dclient: DriverClient) -> Result<Self> {
+        let msm_cfg = MSMConfig::msm_cfg(init.curve, init.group, init.mem_type);
+        if msm_cfg.result_point_size > RESULT_WINDOW_SIZE {
+            return Err(DriverClientError::UnsupportedGroup {
+                group: format!("{:?}", init.group),
+            });
+        }
+        check_precompute_factor(msm_cfg.scalar_size * 8, init.precompute_factor)?;
+        Ok(MSMClient {
+            mem_type: init.mem_type,
+            curve: init.curve,
+            group: init.group,
+            precompute_factor: init.precompute_factor,
+            msm_cfg,
+            tuning: init.tuning,
+            tasks: TaskQueue::default(),
+            hbm: Mutex::new(HbmAllocator::new(dclient.cfg.hbm_size)),
+            hbm_verify: HbmVerify::Off,
+            core_clock_hz: DEFAULT_CORE_CLOCK_HZ,
+            precompute_checked: AtomicBool::new(false),
+            driver_client: dclient,
+        })
+    }
+
+    /// Overrides the maximum number of elements in one hardware task,
+    /// e.g. for an image built with a smaller limit than [`MAX_NOF_ELEMENTS`].
+    pub fn with_max_nof_elements(mut self, max_nof_elements: usize) -> Self {
+        self.msm_cfg.max_nof_elements = max_nof_elements.max(1);
+        self
+    }
+
+    /// The maximum number of elements in one hardware task.
+    pub fn max_nof_elements(&self) -> usize {
+        self.msm_cfg.max_nof_elements
+    }
+
+    /// Checks that the loaded image computes MSM over the curve and the group of the client,
+    /// returns [`DriverClientError::ImageMismatch`] otherwise.
+    /// Returns [`DriverClientError::PrecomputeFactorMismatch`] if the image expects bases
+    /// with another number of precomputed multiples.
+    pub fn check_image(&self) -> Result<()> {
+        let params = self.image_params()?;
+        let image = MSMImageParametrs::parse_image_params(params);
+        if image.curve() != Some(self.curve) || image.group() != self.group {
+            return Err(DriverClientError::ImageMismatch {
+                curve: format!("{:?}", self.curve),
+                group: format!("{:?}", self.group),
+                image_params: params,
+            });
+        }
+        self.check_precompute_factor()
+    }
+
+    /// Returns [`DriverClientError::PrecomputeFactorMismatch`] if the loaded image
+    /// is built for another precompute factor than the client.
+    fn check_precompute_factor(&self) -> Result<()> {
+        let image = MSMImageParametrs::parse_image_params(self.image_params()?);
+        if image.precompute_factor() != self.precompute_factor {
+            return Err(DriverClientError::PrecomputeFactorMismatch {
+                client: self.precompute_factor,
+                image: image.precompute_factor(),
+            });
+        }
+        Ok(())
+    }
+
+    fn image_params(&self) -> Result<u32> {
+        self.driver_client.ctrl_read_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_IMAGE_PARAMTERS,
+        )
+    }
+
+    /// Runs a whole MSM: initializes a task, sets data, waits for the result and reads it.
+    ///
+    /// An MSM larger than [`MSMClient::max_nof_elements`] is split into several hardware tasks
+    /// which are run with [`MSMClient::run_batch`].
+    /// Partial results are decoded, added up on the host and encoded back into the result format
+    /// of the core, so the caller gets the same [`MSMResult`] as for a single task.
+    /// Bases of a part are taken from `hbm_point_addr` moved to the first base of the part.
+    pub fn run(&self, input: MSMInput) -> Result<MSMResult> {
+        let nof_elements = input.params.nof_elements as usize;
+        let max_nof_elements = self.msm_cfg.max_nof_elements;
+        if nof_elements <= max_nof_elements {
+            return self.submit(input)?.wait();
+        }
+
+        let scalar_size = self.msm_cfg.scalar_size;
+        let point_size = self.msm_cfg.point_size.unwrap() * self.precompute_factor as usize;
+        check_input_size(input.scalars.len(), nof_elements * scalar_size)?;
+        if let Some(p) = input.points {
+            check_input_size(p.len(), nof_elements * point_size)?;
+        }
+
+        let tuning = self.task_tuning(&input.params);
+        let mut parts = Vec::with_capacity(nof_elements.div_ceil(max_nof_elements));
+        for start in (0..nof_elements).step_by(max_nof_elements) {
+            let end = (start + max_nof_elements).min(nof_elements);
+            log::debug!("MSM part from {} to {}", start, end);
+            let (p_start, p_end) = (start * point_size, end * point_size);
+            parts.push(MSMInput {
+                points: input.points.map(|p| &p[p_start..p_end]),
+                scalars: &input.scalars[start * scalar_size..end * scalar_size],
+                params: MSMParams {
+                    nof_elements: (end - start) as u32,
+                    // The core reads bases of the part from its own start address.
+                    hbm_point_addr: input
+                        .params
+                        .hbm_point_addr
+                        .map(|(addr, offset)| (addr + p_start as u64, offset)),
+                    tuning: Some(tuning.offset_coefficients((start * scalar_size) as u64)),
+                },
+            });
+        }
+        let results = self.run_batch(&parts)?;
+
+        Ok(MSMResult {
+            result: combine_results(self.curve, self.group, &results)?,
+            result_label: results.last().unwrap().result_label,
+        })
+    }
+
+    /// Takes a buffer for points of `nof_elements` bases (including precomputed ones)
+    /// from the DMA buffer pool of the driver client.
+    /// The buffer goes back to the pool when dropped, so repeated tasks reuse the same memory.
+    pub fn points_buffer(&self, nof_elements: usize) -> Result<PooledDmaBuffer<'_>> {
+        let size =
+            nof_elements * self.msm_cfg.point_size.unwrap() * self.precompute_factor as usize;
+        self.driver_client.buffer_pool.acquire(size)
+    }
+
+    /// Takes a buffer for `nof_elements` scalars from the DMA buffer pool of the driver client.
+    pub fn scalars_buffer(&self, nof_elements: usize) -> Result<PooledDmaBuffer<'_>> {
+        self.driver_client
+            .buffer_pool
+            .acquire(nof_elements * self.msm_cfg.scalar_size)
+    }
+
+    /// Splits the data streamed to the core into chunks of the tuned size:
+    /// scalars unless they are in HBM, and points in DMA mode.
+    pub(super) fn stream_segments<'b>(&self, data: &MSMInput<'b>) -> Vec<(u64, &'b [u8])> {
+        let tuning = self.task_tuning(&data.params);
+        let chunk_size = tuning.dma_chunk_size;
+        let chunks = (data.params.nof_elements as usize).div_ceil(chunk_size);
+
+        let payload_size_scalars = chunk_size * self.msm_cfg.scalar_size;
+        // Scalar addres can be loaded from configuration file or setup by user in input parametrs
+        let s_addr = self.msm_cfg.dma_scalars_addr.unwrap();
+        let stream_scalars = tuning.coefficients_source == CoefficientsSource::DMA;
+        let stream_points = match (data.points, data.params.hbm_point_addr) {
+            (Some(p), None) => Some(p),
+            _ => None,
+        };
+        log::debug!(
+            "Set scalars: {}, set points: {}",
+            stream_scalars,
+            stream_points.is_some()
+        );
+
+        let mut segments: Vec<(u64, &[u8])> = Vec::with_capacity(2 * chunks);
+        let payload_size_points =
+            chunk_size * self.msm_cfg.point_size.unwrap() * self.precompute_factor as usize;
+        for i in 0..chunks {
+            if stream_scalars {
+                segments.push((s_addr, chunk(data.scalars, i, chunks, payload_size_scalars)));
+            }
+            if let Some(p) = stream_points {
+                let p_addr = self.msm_cfg.dma_points_addr.unwrap();
+                segments.push((p_addr, chunk(p, i, chunks, payload_size_points)));
+            }
+        }
+        segments
+    }
+
+    /// The tuning of a task: its own one or the tuning of the client.
+    pub(super) fn task_tuning(&self, params: &MSMParams) -> MSMTuning {
+        params.tuning.unwrap_or(self.tuning)
+    }
+
+    /// Checks the chunk size and writes the optimizations register.
+    /// This is only a readback of the register: bits which the image doesn't implement
+    /// are expected to read back as zero.
+    fn set_optimizations(&self, tuning: &MSMTuning) -> Result<()> {
+        let chunk_bytes = tuning.dma_chunk_size * self.msm_cfg.scalar_size;
+        if tuning.dma_chunk_size == 0 || !chunk_bytes.is_multiple_of(DMA_BEAT_SIZE) {
+            return Err(DriverClientError::InvalidTuning {
+                reason: format!(
+                    "DMA chunk of {} elements is not a multiple of {} bytes",
+                    tuning.dma_chunk_size, DMA_BEAT_SIZE
+                ),
+            });
+        }
+
+        self.driver_client.ctrl_write_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_CPU2HIF_C_OPTIMIZATIONS,
+            tuning.optimizations,
+        )?;
+        let supported = self.driver_client.ctrl_read_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_CPU2HIF_C_OPTIMIZATIONS,
+        )?;
+        if supported != tuning.optimizations {
+            return Err(DriverClientError::InvalidTuning {
+                reason: format!(
+                    "optimizations {:#X} are not supported by the image, it keeps {:#X}",
+                    tuning.optimizations, supported
+                ),
+            });
+        }
+        Ok(())
+    }
+
+    /// Checks whether the result of the oldest completed task is ready to be popped.
+    pub(super) fn is_result_valid(&self) -> Result<bool> {
+        let mut result_valid = [0, 0, 0, 0];
+        self.driver_client.ctrl_read(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_RESULT_VALID,
+            &mut result_valid,
+        )?;
+        Ok(result_valid != [0, 0, 0, 0])
+    }
+
+    /// Switches the core to bases in HBM starting from `addr`.
+    pub(super) fn set_hbm_bases_addr(&self, addr: u64) -> Result<()> {
+        self.driver_client.ctrl_write_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_CPU2HIF_C_BASES_SOURCE,
+            1,
+        )?;
+        self.driver_client.ctrl_write(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_CPU2HIF_C_BASES_HBM_START_ADDRESS_LO,
+            &addr.to_le_bytes(),
+        )
+    }
+
     pub fn task_label(&self) -> Result<u32> {
         self.driver_client.ctrl_read_u32(
             self.driver_client.cfg.ctrl_baseaddr,
@@ -282,6 +569,20 @@ impl MSMClient {
         )
     }
 
+    pub fn nof_pending_tasks(&self) -> Result<u32> {
+        self.driver_client.ctrl_read_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_NOF_PENDING_TASKS_IN_QUEUE,
+        )
+    }
+
+    pub fn nof_pending_results(&self) -> Result<u32> {
+        self.driver_client.ctrl_read_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_NOF_PENDING_RESULTS_IN_QUEUE,
+        )
+    }
+
     pub fn nof_elements(&self) -> Result<u32> {
         self.driver_client.ctrl_read_u32(
             self.driver_client.cfg.ctrl_baseaddr,
@@ -296,19 +597,83 @@ impl MSMClient {
         )
     }
 
+    /// Loads points into HBM at address `addr` with an `offset` and sets `addr` as the start
+    /// of bases for the next task. The upload is verified according to [`MSMClient::with_hbm_verify`].
+    /// Returns [`DriverClientError::HbmRegionOverlap`] if the points would overwrite a resident base set.
     pub fn load_data_to_hbm(&self, points: &[u8], addr: u64, offset: u64) -> Result<()> {
+        self.check_hbm_write(addr + offset, points.len())?;
+        self.write_hbm_points(points, addr, offset)
+    }
+
+    /// [`MSMClient::load_data_to_hbm`] into a region which is already taken by the caller.
+    pub(super) fn write_hbm_points(&self, points: &[u8], addr: u64, offset: u64) -> Result<()> {
         log::debug!("HBM adress: {:#X?}", &addr);
-        self.driver_client.ctrl_write_u32(
-            self.driver_client.cfg.ctrl_baseaddr,
-            INGO_MSM_ADDR::ADDR_CPU2HIF_C_BASES_SOURCE,
-            1,
-        )?;
-        self.driver_client.ctrl_write(
-            self.driver_client.cfg.ctrl_baseaddr,
-            INGO_MSM_ADDR::ADDR_CPU2HIF_C_BASES_HBM_START_ADDRESS_LO,
-            &addr.to_le_bytes(),
-        )?;
-        self.driver_client.dma_write(addr, offset, points)?;
+        self.set_hbm_bases_addr(addr)?;
+        self.driver_client.dma_write_striped(addr, offset, points)?;
+        self.check_upload(points, addr + offset)
+    }
+
+    /// Sets data for an MSM task from files which hold points and scalars in the same layout as [`MSMInput`].
+    ///
+    /// Files are memory-mapped and fed into [`DriverPrimitive::set_data`] by windows of
+    /// [`FILE_WINDOW_ELEMENTS`] elements. Pages of a window are released after it is written,
+    /// so the whole input is never materialized in RAM.
+    /// Points may be `None` if they were already loaded into HBM.
+    pub fn set_data_from_files(
+        &self,
+        points: Option<&Path>,
+        scalars: &Path,
+        params: MSMParams,
+    ) -> Result<()> {
+        let nof_elements = params.nof_elements as usize;
+        let scalar_size = self.msm_cfg.scalar_size;
+        let point_size = self.msm_cfg.point_size.unwrap() * self.precompute_factor as usize;
+
+        let scalars = MappedFile::open(scalars)?;
+        check_input_size(scalars.len(), nof_elements * scalar_size)?;
+        let points = points.map(MappedFile::open).transpose()?;
+        if let Some(p) = points.as_ref() {
+            check_input_size(p.len(), nof_elements * point_size)?;
+        }
+
+        let tuning = self.task_tuning(&params);
+        for start in (0..nof_elements).step_by(FILE_WINDOW_ELEMENTS) {
+            let end = (start + FILE_WINDOW_ELEMENTS).min(nof_elements);
+            log::debug!("Set elements from {} to {}", start, end);
+            let (s_start, s_end) = (start * scalar_size, end * scalar_size);
+            let (p_start, p_end) = (start * point_size, end * point_size);
+
+            self.set_data(MSMInput {
+                points: points.as_ref().map(|p| &p[p_start..p_end]),
+                scalars: &scalars[s_start..s_end],
+                params: MSMParams {
+                    nof_elements: (end - start) as u32,
+                    hbm_point_addr: params
+                        .hbm_point_addr
+                        .map(|(addr, offset)| (addr, offset + p_start as u64)),
+                    tuning: Some(tuning.offset_coefficients(s_start as u64)),
+                },
+            })?;
+
+            scalars.release(s_start, s_end);
+            if let Some(p) = points.as_ref() {
+                p.release(p_start, p_end);
+            }
+        }
+        Ok(())
+    }
+
+    /// Loads points from a file into HBM at address `addr` with an `offset`.
+    /// The file is memory-mapped and written by windows of [`FILE_WINDOW_BYTES`] bytes,
+    /// pages of a window are released after it is written.
+    pub fn load_data_to_hbm_from_file(&self, points: &Path, addr: u64, offset: u64) -> Result<()> {
+        let points = MappedFile::open(points)?;
+        self.check_hbm_write(addr + offset, points.len())?;
+        for start in (0..points.len()).step_by(FILE_WINDOW_BYTES) {
+            let end = (start + FILE_WINDOW_BYTES).min(points.len());
+            self.load_data_to_hbm(&points[start..end], addr, offset + start as u64)?;
+            points.release(start, end);
+        }
         Ok(())
     }
 
@@ -316,7 +681,8 @@ impl MSMClient {
         log::debug!("HBM adress: {:#X?}", addr);
         log::debug!("Data length: {:#X?}", data_len);
         let mut res = vec![0; data_len];
-        self.driver_client.dma_read(addr, offset, &mut res)?;
+        self.driver_client
+            .dma_read_striped(addr, offset, &mut res)?;
         log::debug!("Successfully read data from hbm");
         Ok(res)
     }
@@ -330,6 +696,22 @@ impl MSMClient {
     }
 }
 
+pub(super) fn check_input_size(actual: usize, expected: usize) -> Result<()> {
+    if actual < expected {
+        return Err(DriverClientError::InputSizeMismatch { expected, actual });
+    }
+    Ok(())
+}
+
+/// Returns `i`-th of `chunks` payloads of `data`, the last one takes the rest of the data.
+fn chunk(data: &[u8], i: usize, chunks: usize, payload_size: usize) -> &[u8] {
+    if i != chunks - 1 {
+        &data[i * payload_size..(i + 1) * payload_size]
+    } else {
+        &data[i * payload_size..]
+    }
+}
+
 #[derive(PackedStruct, Debug)]
 #[packed_struct(bit_numbering = "msb0")]
 pub struct MSMImageParametrs {
@@ -347,6 +729,45 @@ pub struct MSMImageParametrs {
     pub hif2_cpu_c_place_holder: u8,
 }
 
+impl MSMImageParametrs {
+    /// Whether points of the image have coordinates in a quadratic extension field.
+    pub fn is_complex(&self) -> bool {
+        self.hif2_cpu_c_curve & 1 == 1
+    }
+
+    /// The group of points of the image, G2 images are complex.
+    pub fn group(&self) -> MSMGroup {
+        if self.is_complex() {
+            MSMGroup::G2
+        } else {
+            MSMGroup::G1
+        }
+    }
+
+    /// The curve of the image, `None` if the curve id is unknown.
+    ///
+    /// The curve id is in bits 2..=6 of the curve field, below it are flags
+    /// (bit 0 is [`MSMImageParametrs::is_complex`]), so the id is shifted down before matching.
+    pub fn curve(&self) -> Option<Curve> {
+        match (self.hif2_cpu_c_curve & 0b1111100) >> 2 {
+            0 => Some(Curve::BLS377),
+            1 => Some(Curve::BN254),
+            2 => Some(Curve::BLS381),
+            _ => None,
+        }
+    }
+
+    /// The precompute factor of the image, taken from the number of segments.
+    ///
+    /// A precompute image splits every scalar into segments of equal bit size and reads
+    /// a precomputed multiple of the base for each segment, see [`precompute_bases`](super::precompute_bases).
+    /// So the number of segments is the number of points per base the image expects,
+    /// which is the precompute factor. Images without precomputation report 0 or 1 segments.
+    pub fn precompute_factor(&self) -> u32 {
+        (self.hif2_cpu_c_number_of_segments as u32).max(PRECOMPUTE_FACTOR_BASE)
+    }
+}
+
 impl ParametersAPI for MSMImageParametrs {
     fn parse_image_params(params: u32) -> MSMImageParametrs {
         let buf = params.reverse_bits().to_be_bytes();
@@ -355,12 +776,12 @@ impl ParametersAPI for MSMImageParametrs {
 
     fn debug_information(&self) {
         log::debug!("Is Stub: {:?}", self.hif2cpu_c_is_stub);
-        log::debug!("Is curve complex {:?}", self.hif2_cpu_c_curve & 1 == 1);
-        match self.hif2_cpu_c_curve & 0b1111100 {
-            0 => log::debug!("This is BLS12_377 curve"),
-            1 => log::debug!("This is BN254 curve"),
-            2 => log::debug!("This is BLS12_381 curve"),
-            _ => log::debug!("This is UNKNOWN curve"),
+        log::debug!("Is curve complex {:?}", self.is_complex());
+        match self.curve() {
+            Some(Curve::BLS377) => log::debug!("This is BLS12_377 curve"),
+            Some(Curve::BN254) => log::debug!("This is BN254 curve"),
+            Some(Curve::BLS381) => log::debug!("This is BLS12_381 curve"),
+            None => log::debug!("This is UNKNOWN curve"),
         }
         log::debug!(
             "Number of EC addreses: {:?}",
@@ -377,3 +798,50 @@ impl ParametersAPI for MSMImageParametrs {
         log::debug!("Place Holder: {:?}", self.hif2_cpu_c_place_holder);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Parameter words as read from `ADDR_HIF2CPU_C_IMAGE_PARAMTERS`. The curve field holds
+    // the complex flag in bit 0 and the curve id in bits 2..=6.
+    /// BN254 G1 image: curve field `0b0000100`, 4 EC adders, buckets address width 16, 1 segment.
+    const BN254_G1_IMAGE: u32 = 0x0202_0880;
+    /// BLS12-381 G2 image: curve field `0b0001001`, 2 EC adders, buckets address width 14, 8 segments.
+    const BLS381_G2_IMAGE: u32 = 0x0904_7010;
+
+    #[test]
+    fn parse_image_parameters() {
+        let image = MSMImageParametrs::parse_image_params(BN254_G1_IMAGE);
+        assert_eq!(image.hif2_cpu_c_curve, 0b0000100);
+        assert_eq!(image.hif2_cpu_c_number_of_ec_adders, 4);
+        assert_eq!(image.hif2_cpu_c_buckets_mem_addr_width, 16);
+        assert_eq!(image.curve(), Some(Curve::BN254));
+        assert_eq!(image.group(), MSMGroup::G1);
+
+        let image = MSMImageParametrs::parse_image_params(BLS381_G2_IMAGE);
+        assert_eq!(image.hif2_cpu_c_curve, 0b0001001);
+        assert_eq!(image.curve(), Some(Curve::BLS381));
+        assert_eq!(image.group(), MSMGroup::G2);
+        assert_eq!(image.hif2_cpu_c_number_of_segments, 8);
+        assert_eq!(image.precompute_factor(), PRECOMPUTE_FACTOR);
+
+        let image = MSMImageParametrs::parse_image_params(BN254_G1_IMAGE);
+        assert_eq!(image.precompute_factor(), PRECOMPUTE_FACTOR_BASE);
+        // An image which doesn't report segments has no precomputation.
+        let image = MSMImageParametrs::parse_image_params(BN254_G1_IMAGE & !0x80);
+        assert_eq!(image.hif2_cpu_c_number_of_segments, 0);
+        assert_eq!(image.precompute_factor(), PRECOMPUTE_FACTOR_BASE);
+    }
+
+    #[test]
+    fn results_fit_result_registers() {
+        assert_eq!(RESULT_WINDOW_SIZE, 144);
+        for curve in [Curve::BLS377, Curve::BLS381, Curve::BN254] {
+            let g1 = MSMConfig::msm_cfg(curve, MSMGroup::G1, PointMemoryType::DMA);
+            assert!(g1.result_point_size <= RESULT_WINDOW_SIZE);
+            let g2 = MSMConfig::msm_cfg(curve, MSMGroup::G2, PointMemoryType::DMA);
+            assert!(g2.result_point_size > RESULT_WINDOW_SIZE);
+        }
+    }
+}
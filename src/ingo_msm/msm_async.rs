@@ -0,0 +1,50 @@
+//! Async versions of the data transfer and result methods of [`MSMClient`].
+//!
+//! All methods are cancel-safe: a dropped [`MSMClient::set_data_async`] completes the stream
+//! of a started transfer, and a result is popped from the core in the same poll it is read.
+use super::{msm_api::*, msm_task::MsmTaskHandle};
+use crate::{driver_client::*, error::*};
+
+impl MSMClient {
+    /// Async version of [`DriverPrimitive::set_data`].
+    ///
+    /// The data is written piece by piece between polls. Points which go to HBM are written
+    /// before scalars, so if the future is dropped after the transfer is started,
+    /// both are written to the end and the pushed task gets its whole input.
+    pub async fn set_data_async(&self, data: MSMInput<'_>) -> Result<()> {
+        let mut transfer = WriteSegments::new(&self.driver_client);
+        if let (Some(p), Some((addr, offset))) = (data.points, data.params.hbm_point_addr) {
+            log::debug!("HBM adress: {:#X?}", &addr);
+            self.check_hbm_write(addr + offset, p.len())?;
+            self.set_hbm_bases_addr(addr)?;
+            transfer = transfer.memory(&[(addr + offset, p)]);
+        }
+        if let CoefficientsSource::HBM { addr } = self.task_tuning(&data.params).coefficients_source
+        {
+            self.check_hbm_write(addr, data.scalars.len())?;
+            transfer = transfer.memory(&[(addr, data.scalars)]);
+        }
+        transfer.stream(&self.stream_segments(&data)).await
+    }
+
+    /// Async version of [`DriverPrimitive::wait_result`], polls the result valid register.
+    pub async fn wait_result_async(&self) -> Result<()> {
+        poll_device(|| Ok(self.is_result_valid()?.then_some(()))).await
+    }
+
+    /// Async version of [`DriverPrimitive::result`].
+    ///
+    /// The result is read and popped in a single poll, so it is never lost by a dropped future.
+    pub async fn result_async(&self) -> Result<Option<MSMResult>> {
+        self.result(None)
+    }
+}
+
+impl MsmTaskHandle<'_> {
+    /// Async version of [`MsmTaskHandle::wait`].
+    ///
+    /// If the future is dropped, the handle is dropped too and the result is discarded when it arrives.
+    pub async fn wait_async(mut self) -> Result<MSMResult> {
+        poll_device(|| self.try_result()).await
+    }
+}
@@ -0,0 +1,52 @@
+//! A common interface of MSM backends.
+//!
+//! [`MSMClient`] computes MSM on the card and [`CpuMSM`](super::CpuMSM) on the host.
+//! Both take [`MSMInput`] in the same byte layout and return [`MSMResult`] in the result
+//! format of the core, so callers can switch between them or compare their outputs.
+//!
+//! An input without points reads bases which are already in HBM of the card. [`CpuMSM`](super::CpuMSM)
+//! computes such inputs only over host copies of the bases registered with
+//! [`CpuMSM::with_resident_bases`](super::CpuMSM::with_resident_bases), otherwise it returns
+//! [`DriverClientError::MissingPoints`].
+use super::{msm_api::*, msm_cfg::*};
+use crate::error::*;
+
+/// A backend which computes MSM over inputs in the layout of the MSM core.
+pub trait MSMBackend: Send + Sync {
+    fn curve(&self) -> Curve;
+
+    fn group(&self) -> MSMGroup;
+
+    /// Where the backend takes bases of inputs with [`MSMParams::hbm_point_addr`] from,
+    /// it selects the size of bases in the input.
+    fn mem_type(&self) -> PointMemoryType;
+
+    /// Every base in the input is followed by `precompute_factor - 1` precomputed multiples.
+    fn precompute_factor(&self) -> u32;
+
+    /// Computes a whole MSM, see [`MSMClient::run`].
+    /// Inputs without points are computed by CPU backends only if they have host copies of the bases.
+    fn run(&self, input: MSMInput) -> Result<MSMResult>;
+}
+
+impl MSMBackend for MSMClient {
+    fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    fn group(&self) -> MSMGroup {
+        self.group
+    }
+
+    fn mem_type(&self) -> PointMemoryType {
+        self.mem_type
+    }
+
+    fn precompute_factor(&self) -> u32 {
+        self.precompute_factor
+    }
+
+    fn run(&self, input: MSMInput) -> Result<MSMResult> {
+        MSMClient::run(self, input)
+    }
+}
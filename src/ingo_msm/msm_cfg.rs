@@ -1,18 +1,29 @@
 use strum_macros::EnumString;
 
-#[derive(Debug, EnumString, PartialEq)]
+#[derive(Debug, Copy, Clone, EnumString, PartialEq, Eq)]
 pub enum Curve {
     BLS377,
     BLS381,
     BN254,
 }
 
+/// The group of points of an MSM. G2 points have coordinates in a quadratic extension
+/// of the base field, which MSM images report as a "complex" curve.
+#[derive(Debug, Copy, Clone, EnumString, PartialEq, Eq)]
+pub enum MSMGroup {
+    G1,
+    G2,
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy, EnumString)]
 pub enum PointMemoryType {
     HBM,
     DMA,
 }
 
+/// The maximum number of elements in one task of the current MSM images.
+pub const MAX_NOF_ELEMENTS: usize = 1 << 26;
+
 #[derive(Debug, Copy, Clone)]
 pub(super) struct MSMConfig {
     // The size characteristic in points and scalars in a curve.
@@ -23,22 +34,43 @@ pub(super) struct MSMConfig {
     /// The size of scalar coordinate in bytes.
     pub scalar_size: usize,
 
+    /// The maximum number of elements in one task of the MSM core.
+    pub max_nof_elements: usize,
+
     // Ingo MSM Core additional addresses
     pub dma_scalars_addr: Option<u64>,
     pub dma_points_addr: Option<u64>,
 }
 
 impl MSMConfig {
-    pub(super) fn msm_cfg(curve: Curve, mem: PointMemoryType) -> Self {
+    /// Returns the configuration for the curve, the group and the memory of points.
+    pub(super) fn msm_cfg(curve: Curve, group: MSMGroup, mem: PointMemoryType) -> Self {
+        let cfg = Self::g1_cfg(curve, mem);
+        match group {
+            MSMGroup::G1 => cfg,
+            MSMGroup::G2 => cfg.g2(),
+        }
+    }
+
+    fn g1_cfg(curve: Curve, mem: PointMemoryType) -> Self {
         match (curve, mem) {
             (Curve::BLS377, PointMemoryType::HBM) => msm_bls377_hbm_cfg(),
             (Curve::BLS377, PointMemoryType::DMA) => msm_bls377_dma_cfg(),
             (Curve::BLS381, PointMemoryType::HBM) => msm_bls381_hbm_cfg(),
             (Curve::BLS381, PointMemoryType::DMA) => msm_bls381_dma_cfg(),
-            (Curve::BN254, PointMemoryType::HBM) => todo!(),
+            (Curve::BN254, PointMemoryType::HBM) => msm_bn254_hbm_cfg(),
             (Curve::BN254, PointMemoryType::DMA) => msm_bn254_dma_cfg(),
         }
     }
+
+    /// G2 coordinates are pairs of base field elements, so points are twice as large as in G1.
+    fn g2(self) -> Self {
+        MSMConfig {
+            result_point_size: 2 * self.result_point_size,
+            point_size: self.point_size.map(|size| 2 * size),
+            ..self
+        }
+    }
 }
 
 fn msm_bls377_hbm_cfg() -> MSMConfig {
@@ -46,6 +78,7 @@ fn msm_bls377_hbm_cfg() -> MSMConfig {
         result_point_size: 144,
         point_size: Some(96),
         scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
         dma_scalars_addr: Some(0x0000020000000000),
         dma_points_addr: None,
     }
@@ -56,6 +89,7 @@ fn msm_bls377_dma_cfg() -> MSMConfig {
         result_point_size: 144,
         point_size: Some(96),
         scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
         dma_scalars_addr: Some(0x0000020000000000),
         dma_points_addr: Some(0x0000010000000000),
     }
@@ -66,6 +100,7 @@ fn msm_bls381_hbm_cfg() -> MSMConfig {
         result_point_size: 144,
         point_size: Some(96),
         scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
         dma_scalars_addr: Some(0x0000020000000000),
         dma_points_addr: None,
     }
@@ -76,16 +111,29 @@ fn msm_bls381_dma_cfg() -> MSMConfig {
         result_point_size: 144,
         point_size: Some(96),
         scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
         dma_scalars_addr: Some(0x0000020000000000),
         dma_points_addr: Some(0x0000010000000000),
     }
 }
 
+fn msm_bn254_hbm_cfg() -> MSMConfig {
+    MSMConfig {
+        result_point_size: 96,
+        point_size: Some(64),
+        scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
+        dma_scalars_addr: Some(0x0000010000000000),
+        dma_points_addr: None,
+    }
+}
+
 fn msm_bn254_dma_cfg() -> MSMConfig {
     MSMConfig {
         result_point_size: 96,
         point_size: Some(64),
         scalar_size: 32,
+        max_nof_elements: MAX_NOF_ELEMENTS,
         dma_scalars_addr: Some(0x0000010000000000),
         dma_points_addr: Some(0x0000000000000000),
     }
@@ -0,0 +1,559 @@
+//! Sampled verification of MSM results.
+//!
+//! Every result is decoded and checked to be a point of the prime order subgroup.
+//! A sample of tasks is also checked by one of [`MSMCheckMethod`]:
+//!
+//! - A split check cuts the input at a random point, both parts are computed by the same backend
+//!   and their sum must match the result of the whole task. It needs no pairings and no MSM
+//!   on the host, but it only detects transient faults: a fault which repeats for the same inputs,
+//!   such as corrupted bases resident in HBM, a wrong `hbm_point_addr` or a systematic bug
+//!   of the core, gives consistent parts and passes the check.
+//! - A CPU window check computes a random window of the input on the backend and on the CPU
+//!   and compares the results, so it also detects deterministic faults of the backend.
+//!   It costs an MSM of the window on the host and needs points in the input or host copies
+//!   of the bases in HBM, see [`CheckedMSM::with_resident_bases`].
+//!
+//! Bases resident in HBM themselves are checked by [`MSMClient::verify_resident_bases`]. On a mismatch the task can be recomputed on the CPU.
+use super::{
+    msm_api::*,
+    msm_backend::*,
+    msm_cfg::*,
+    msm_cpu::{CpuMSM, HostBases},
+    msm_curve::*,
+};
+use crate::error::*;
+
+use ark_std::rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// How a sampled task is checked.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MSMCheckMethod {
+    /// Split the input at a random point and compute both parts on the backend.
+    /// Detects only transient faults.
+    Split,
+    /// Compute a random window of up to `nof_elements` elements on the backend and on the CPU.
+    /// Tasks whose bases the CPU doesn't have are checked by splitting.
+    CpuWindow { nof_elements: u32 },
+}
+
+/// Settings of [`CheckedMSM`].
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MSMCheckConfig {
+    /// Fraction of tasks from 0 to 1 which are checked by `method`.
+    pub rate: f64,
+    pub method: MSMCheckMethod,
+    /// Recompute the task on the CPU if a check fails, otherwise
+    /// [`DriverClientError::MSMCheckFailed`] is returned. Tasks which read bases from HBM
+    /// are recomputed only if host copies of the bases are registered with
+    /// [`CheckedMSM::with_resident_bases`].
+    pub fallback_to_cpu: bool,
+}
+
+impl Default for MSMCheckConfig {
+    fn default() -> Self {
+        MSMCheckConfig {
+            rate: 0.01,
+            method: MSMCheckMethod::Split,
+            fallback_to_cpu: true,
+        }
+    }
+}
+
+/// A failed check of a task.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MSMMismatch {
+    /// A hash of points, scalars and parameters of the input.
+    pub fingerprint: u64,
+    pub nof_elements: u32,
+    /// The split point of a split check.
+    pub split_at: Option<u32>,
+    /// Elements of a CPU window check.
+    pub window: Option<Range<u32>>,
+    /// The checked result: of the whole task, or of the window computed by the backend.
+    pub result: Vec<u8>,
+    /// The sum of results of the parts or the CPU result of the window, in the same format.
+    /// `None` if the result itself is not a valid point.
+    pub recomputed: Option<Vec<u8>>,
+}
+
+/// Counters of checks done by a [`CheckedMSM`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MSMCheckStats {
+    pub nof_tasks: usize,
+    /// Tasks checked by splitting.
+    pub nof_split_checks: usize,
+    /// Tasks checked by a CPU window.
+    pub nof_cpu_checks: usize,
+    pub nof_mismatches: usize,
+    /// Tasks recomputed on the CPU after a failed check.
+    pub nof_fallbacks: usize,
+}
+
+/// A check chosen for a sampled task.
+enum Sample {
+    Split(u32),
+    Window(Range<u32>),
+}
+
+/// An MSM backend which verifies results of another backend, usually an [`MSMClient`].
+pub struct CheckedMSM<B: MSMBackend> {
+    backend: B,
+    cpu: CpuMSM,
+    cfg: MSMCheckConfig,
+    /// Sizes in bytes of a base with its precomputed multiples and of a scalar.
+    element_size: usize,
+    scalar_size: usize,
+    rng: Mutex<StdRng>,
+    stats: Mutex<MSMCheckStats>,
+    mismatches: Mutex<Vec<MSMMismatch>>,
+}
+
+impl<B: MSMBackend> CheckedMSM<B> {
+    pub fn new(backend: B, cfg: MSMCheckConfig) -> Result<Self> {
+        let cpu = CpuMSM::new(MSMInit {
+            mem_type: backend.mem_type(),
+            precompute_factor: backend.precompute_factor(),
+            curve: backend.curve(),
+            group: backend.group(),
+            tuning: MSMTuning::default(),
+        })?;
+        // Parts of inputs in HBM shift `hbm_point_addr` by elements in the layout of the backend.
+        let msm_cfg = MSMConfig::msm_cfg(backend.curve(), backend.group(), backend.mem_type());
+        let element_size = msm_cfg.point_size.unwrap() * backend.precompute_factor() as usize;
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Ok(CheckedMSM {
+            backend,
+            cpu,
+            cfg,
+            element_size,
+            scalar_size: msm_cfg.scalar_size,
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+            stats: Mutex::new(MSMCheckStats::default()),
+            mismatches: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Registers a host copy of bases loaded into HBM at `addr` for CPU window checks
+    /// and the CPU fallback of tasks without points, see [`CpuMSM::with_resident_bases`].
+    pub fn with_resident_bases(mut self, addr: u64, points: HostBases) -> Self {
+        self.cpu = self.cpu.with_resident_bases(addr, points);
+        self
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn stats(&self) -> MSMCheckStats {
+        *self.stats.lock().unwrap()
+    }
+
+    /// Failed checks since the wrapper was created.
+    pub fn mismatches(&self) -> Vec<MSMMismatch> {
+        self.mismatches.lock().unwrap().clone()
+    }
+
+    /// Runs a task on the backend and checks the result.
+    ///
+    /// If the check fails, the mismatch is recorded and the task is recomputed on the CPU
+    /// when [`MSMCheckConfig::fallback_to_cpu`] is set. A task without points which reads bases
+    /// from HBM can be recomputed only if host copies of the bases are registered with
+    /// [`CheckedMSM::with_resident_bases`], otherwise [`DriverClientError::MSMCheckFailed`]
+    /// is returned even with the fallback enabled.
+    /// Checks of tasks which take scalars from HBM by the tuning of the client
+    /// rather than of the task are not supported, their parts would read wrong scalars.
+    pub fn run(&self, input: MSMInput) -> Result<MSMResult> {
+        let result = self.backend.run(input)?;
+        self.stats.lock().unwrap().nof_tasks += 1;
+
+        let nof_elements = input.params.nof_elements;
+        let mut mismatch = MSMMismatch {
+            fingerprint: fingerprint(&input),
+            nof_elements,
+            split_at: None,
+            window: None,
+            result: result.result.clone(),
+            recomputed: None,
+        };
+        if self.is_valid(&result) {
+            match self.sample(&input) {
+                None => return Ok(result),
+                Some(Sample::Split(at)) => {
+                    self.stats.lock().unwrap().nof_split_checks += 1;
+                    let recomputed = self.split_result(input, at)?;
+                    // Results of the core have arbitrary `z`, both are compared normalized.
+                    if recomputed == self.normalize(&result)? {
+                        return Ok(result);
+                    }
+                    mismatch.split_at = Some(at);
+                    mismatch.recomputed = Some(recomputed);
+                }
+                Some(Sample::Window(window)) => {
+                    self.stats.lock().unwrap().nof_cpu_checks += 1;
+                    let part = self.part(&input, window.start as usize, window.end as usize)?;
+                    let device = self.normalize(&self.backend.run(part)?)?;
+                    let cpu = self.normalize(&self.cpu.run(part)?)?;
+                    if device == cpu {
+                        return Ok(result);
+                    }
+                    mismatch.window = Some(window);
+                    mismatch.result = device;
+                    mismatch.recomputed = Some(cpu);
+                }
+            }
+        }
+
+        log::error!(
+            "MSM check of input {:#X} of {} elements failed, split at {:?}, window {:?}",
+            mismatch.fingerprint,
+            nof_elements,
+            mismatch.split_at,
+            mismatch.window
+        );
+        self.mismatches.lock().unwrap().push(mismatch.clone());
+        self.stats.lock().unwrap().nof_mismatches += 1;
+
+        if self.cfg.fallback_to_cpu && self.cpu.input_points(&input).is_some() {
+            log::warn!("Recompute MSM {:#X} on the CPU", mismatch.fingerprint);
+            self.stats.lock().unwrap().nof_fallbacks += 1;
+            let cpu_result = self.cpu.run(input)?;
+            return Ok(MSMResult {
+                result: cpu_result.result,
+                result_label: result.result_label,
+            });
+        }
+        Err(DriverClientError::MSMCheckFailed {
+            fingerprint: mismatch.fingerprint,
+        })
+    }
+
+    /// Picks a check for a task at the configured rate.
+    fn sample(&self, input: &MSMInput) -> Option<Sample> {
+        let nof_elements = input.params.nof_elements;
+        let mut rng = self.rng.lock().unwrap();
+        if nof_elements == 0 || !rng.gen_bool(self.cfg.rate.clamp(0.0, 1.0)) {
+            return None;
+        }
+        match self.cfg.method {
+            MSMCheckMethod::CpuWindow { nof_elements: size }
+                if self.cpu.input_points(input).is_some() =>
+            {
+                let size = size.clamp(1, nof_elements);
+                let start = rng.gen_range(0..=nof_elements - size);
+                Some(Sample::Window(start..start + size))
+            }
+            _ => (nof_elements > 1).then(|| Sample::Split(rng.gen_range(1..nof_elements))),
+        }
+    }
+
+    fn is_valid(&self, result: &MSMResult) -> bool {
+        result
+            .validate(self.backend.curve(), self.backend.group())
+            .is_ok()
+    }
+
+    /// Encodes a result with `z = 1`, so results of the same point are equal.
+    fn normalize(&self, result: &MSMResult) -> Result<Vec<u8>> {
+        combine_results(
+            self.backend.curve(),
+            self.backend.group(),
+            std::slice::from_ref(result),
+        )
+    }
+
+    /// Cuts the elements `start..end` of the input like a task of a split MSM,
+    /// see [`MSMClient::run`].
+    fn part<'a>(&self, input: &MSMInput<'a>, start: usize, end: usize) -> Result<MSMInput<'a>> {
+        let nof_elements = input.params.nof_elements as usize;
+        let (element_size, scalar_size) = (self.element_size, self.scalar_size);
+        check_input_size(input.scalars.len(), nof_elements * scalar_size)?;
+        if let Some(p) = input.points {
+            check_input_size(p.len(), nof_elements * element_size)?;
+        }
+
+        let (p_start, p_end) = (start * element_size, end * element_size);
+        Ok(MSMInput {
+            points: input.points.map(|p| &p[p_start..p_end]),
+            scalars: &input.scalars[start * scalar_size..end * scalar_size],
+            params: MSMParams {
+                nof_elements: (end - start) as u32,
+                hbm_point_addr: input
+                    .params
+                    .hbm_point_addr
+                    .map(|(addr, offset)| (addr + p_start as u64, offset)),
+                tuning: input
+                    .params
+                    .tuning
+                    .map(|t| t.offset_coefficients((start * scalar_size) as u64)),
+            },
+        })
+    }
+
+    /// Computes the parts `0..at` and `at..nof_elements` of the input and adds up their results.
+    fn split_result(&self, input: MSMInput, at: u32) -> Result<Vec<u8>> {
+        let nof_elements = input.params.nof_elements as usize;
+        let results = [
+            self.backend.run(self.part(&input, 0, at as usize)?)?,
+            self.backend
+                .run(self.part(&input, at as usize, nof_elements)?)?,
+        ];
+        combine_results(self.backend.curve(), self.backend.group(), &results)
+    }
+}
+
+impl<B: MSMBackend> MSMBackend for CheckedMSM<B> {
+    fn curve(&self) -> Curve {
+        self.backend.curve()
+    }
+
+    fn group(&self) -> MSMGroup {
+        self.backend.group()
+    }
+
+    fn mem_type(&self) -> PointMemoryType {
+        self.backend.mem_type()
+    }
+
+    fn precompute_factor(&self) -> u32 {
+        self.backend.precompute_factor()
+    }
+
+    fn run(&self, input: MSMInput) -> Result<MSMResult> {
+        CheckedMSM::run(self, input)
+    }
+}
+
+/// A hash of the input of a task, it identifies the input in logs of mismatches.
+fn fingerprint(input: &MSMInput) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    input.points.hash(&mut hasher);
+    input.scalars.hash(&mut hasher);
+    input.params.nof_elements.hash(&mut hasher);
+    input.params.hbm_point_addr.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingo_msm::precompute_bases;
+    use ark_bn254::{Fr, G1Affine, G1Projective};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::{BigInteger, PrimeField};
+    use std::sync::Arc;
+
+    /// A backend which adds the generator to results of tasks of `faulty_size` elements.
+    struct FaultyBackend {
+        cpu: CpuMSM,
+        faulty_size: u32,
+    }
+
+    impl MSMBackend for FaultyBackend {
+        fn curve(&self) -> Curve {
+            Curve::BN254
+        }
+
+        fn group(&self) -> MSMGroup {
+            MSMGroup::G1
+        }
+
+        fn mem_type(&self) -> PointMemoryType {
+            self.cpu.mem_type()
+        }
+
+        fn precompute_factor(&self) -> u32 {
+            PRECOMPUTE_FACTOR_BASE
+        }
+
+        fn run(&self, input: MSMInput) -> Result<MSMResult> {
+            let mut result = self.cpu.run(input)?;
+            if input.params.nof_elements == self.faulty_size {
+                let wrong = result.to_projective::<G1Affine>()?
+                    + G1Affine::prime_subgroup_generator().into_projective();
+                result.result = G1Affine::encode_result(&wrong);
+            }
+            Ok(result)
+        }
+    }
+
+    /// A backend with a systematic fault: every result is doubled.
+    struct DoublingBackend {
+        cpu: CpuMSM,
+    }
+
+    impl MSMBackend for DoublingBackend {
+        fn curve(&self) -> Curve {
+            Curve::BN254
+        }
+
+        fn group(&self) -> MSMGroup {
+            MSMGroup::G1
+        }
+
+        fn mem_type(&self) -> PointMemoryType {
+            self.cpu.mem_type()
+        }
+
+        fn precompute_factor(&self) -> u32 {
+            PRECOMPUTE_FACTOR_BASE
+        }
+
+        fn run(&self, input: MSMInput) -> Result<MSMResult> {
+            let mut result = self.cpu.run(input)?;
+            let wrong = result.to_projective::<G1Affine>()?.double();
+            result.result = G1Affine::encode_result(&wrong);
+            Ok(result)
+        }
+    }
+
+    #[test]
+    fn split_check_detects_faulty_results() {
+        let init = MSMInit {
+            mem_type: PointMemoryType::DMA,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
+            curve: Curve::BN254,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        };
+        let g = G1Affine::prime_subgroup_generator();
+        let bases: Vec<G1Affine> = (1..=16u64).map(|i| g.mul(i).into_affine()).collect();
+        let points = precompute_bases(&bases, PRECOMPUTE_FACTOR_BASE).unwrap();
+        let scalars: Vec<u8> = (0..16u64)
+            .flat_map(|i| Fr::from(i + 2).into_repr().to_bytes_le())
+            .collect();
+        let input = MSMInput {
+            points: Some(&points),
+            scalars: &scalars,
+            params: MSMParams {
+                nof_elements: 16,
+                hbm_point_addr: None,
+                tuning: None,
+            },
+        };
+        let expected: G1Projective = (0..16u64).map(|i| g.mul((i + 1) * (i + 2))).sum();
+
+        let cfg = MSMCheckConfig {
+            rate: 1.0,
+            method: MSMCheckMethod::Split,
+            fallback_to_cpu: true,
+        };
+        let backend = FaultyBackend {
+            cpu: CpuMSM::new(init).unwrap(),
+            faulty_size: 16,
+        };
+        let checked = CheckedMSM::new(backend, cfg).unwrap();
+        let result = checked.run(input).unwrap();
+        assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+        assert_eq!(checked.stats().nof_fallbacks, 1);
+        let mismatches = checked.mismatches();
+        assert_eq!(mismatches.len(), 1);
+        assert_eq!(mismatches[0].fingerprint, fingerprint(&input));
+        assert!(mismatches[0].split_at.is_some());
+
+        let backend = FaultyBackend {
+            cpu: CpuMSM::new(init).unwrap(),
+            faulty_size: 0,
+        };
+        let checked = CheckedMSM::new(backend, cfg).unwrap();
+        checked.run(input).unwrap();
+        assert_eq!(checked.stats().nof_split_checks, 1);
+        assert!(checked.mismatches().is_empty());
+
+        let backend = FaultyBackend {
+            cpu: CpuMSM::new(init).unwrap(),
+            faulty_size: 16,
+        };
+        let cfg = MSMCheckConfig {
+            fallback_to_cpu: false,
+            ..cfg
+        };
+        let checked = CheckedMSM::new(backend, cfg).unwrap();
+        assert!(matches!(
+            checked.run(input),
+            Err(DriverClientError::MSMCheckFailed { .. })
+        ));
+
+        // A systematic fault gives consistent parts, only the CPU window detects it.
+        let checked = CheckedMSM::new(
+            DoublingBackend {
+                cpu: CpuMSM::new(init).unwrap(),
+            },
+            cfg,
+        )
+        .unwrap();
+        assert_eq!(
+            checked
+                .run(input)
+                .unwrap()
+                .to_projective::<G1Affine>()
+                .unwrap(),
+            expected.double()
+        );
+        let cfg = MSMCheckConfig {
+            method: MSMCheckMethod::CpuWindow { nof_elements: 4 },
+            fallback_to_cpu: true,
+            ..cfg
+        };
+        let checked = CheckedMSM::new(
+            DoublingBackend {
+                cpu: CpuMSM::new(init).unwrap(),
+            },
+            cfg,
+        )
+        .unwrap();
+        let result = checked.run(input).unwrap();
+        assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+        assert_eq!(checked.stats().nof_cpu_checks, 1);
+        let window = checked.mismatches()[0].window.clone().unwrap();
+        assert_eq!(window.end - window.start, 4);
+
+        // Inputs which read bases from HBM need host copies of the bases for CPU checks.
+        let hbm_init = MSMInit {
+            mem_type: PointMemoryType::HBM,
+            ..init
+        };
+        let host_bases: HostBases = Arc::new(points.clone());
+        let hbm_input = MSMInput {
+            points: None,
+            params: MSMParams {
+                hbm_point_addr: Some((0x4000, 0)),
+                ..input.params
+            },
+            ..input
+        };
+        let hbm_cpu = || {
+            CpuMSM::new(hbm_init)
+                .unwrap()
+                .with_resident_bases(0x4000, host_bases.clone())
+        };
+        let backend = FaultyBackend {
+            cpu: hbm_cpu(),
+            faulty_size: 16,
+        };
+        let checked = CheckedMSM::new(backend, cfg).unwrap();
+        assert!(matches!(
+            checked.run(hbm_input),
+            Err(DriverClientError::MSMCheckFailed { .. })
+        ));
+        assert_eq!(checked.stats().nof_split_checks, 1);
+
+        let checked = CheckedMSM::new(DoublingBackend { cpu: hbm_cpu() }, cfg)
+            .unwrap()
+            .with_resident_bases(0x4000, host_bases.clone());
+        let result = checked.run(hbm_input).unwrap();
+        assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+        assert_eq!(checked.stats().nof_fallbacks, 1);
+        let window = checked.mismatches()[0].window.clone().unwrap();
+        assert_eq!(window.end - window.start, 4);
+    }
+}
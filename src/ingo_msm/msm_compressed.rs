@@ -0,0 +1,237 @@
+//! Decompression of points stored in compressed form.
+//!
+//! SRS and proving key files usually store only `x` of a point and flags with the sign of `y`.
+//! Points are decompressed in parallel into the layout of [`precompute_bases`], so the result
+//! can be fed to `set_data` or `load_data_to_hbm` directly.
+use super::{msm_api::*, msm_cfg::*, msm_curve::*, msm_precompute::*};
+use crate::{error::*, utils::MappedFile};
+
+use rayon::prelude::*;
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Number of points which are decompressed by one rayon task.
+const DECOMPRESS_BATCH: usize = 2048;
+
+/// Flags of the zcash encoding in the first byte of a point.
+const ZCASH_COMPRESSED: u8 = 1 << 7;
+const ZCASH_INFINITY: u8 = 1 << 6;
+const ZCASH_Y_SIGN: u8 = 1 << 5;
+
+/// Encoding of compressed points.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointCompression {
+    /// Compressed `CanonicalSerialize` of arkworks: little-endian `x` with
+    /// infinity and `y` sign flags in the top bits of the last byte.
+    Arkworks,
+    /// Encoding of zcash and most BLS12 tooling: big-endian `x` (`c1` first in G2) with
+    /// compression, infinity and `y` sign flags in the top bits of the first byte.
+    /// Only BLS12 curves have spare bits for the flags.
+    Zcash,
+}
+
+impl PointCompression {
+    /// The size in bytes of a compressed point of the curve `C`.
+    pub fn point_size<C: MSMCurve>(self) -> usize {
+        match self {
+            PointCompression::Arkworks => C::zero().serialized_size(),
+            PointCompression::Zcash => C::base_size() / 2,
+        }
+    }
+}
+
+/// Decompresses points, checking that every point is on the curve and in the prime order subgroup.
+///
+/// Returns [`DriverClientError::InputSizeMismatch`] if `data` is not a whole number of points
+/// and [`DriverClientError::InvalidCompressedPoint`] with the index of the first invalid point.
+pub fn decompress_points<C: MSMCurve>(
+    data: &[u8],
+    compression: PointCompression,
+) -> Result<Vec<C>> {
+    if compression == PointCompression::Zcash && C::CURVE == Curve::BN254 {
+        return Err(DriverClientError::UnsupportedCompression {
+            curve: format!("{:?}", C::CURVE),
+            compression: format!("{:?}", compression),
+        });
+    }
+    let point_size = compression.point_size::<C>();
+    if !data.len().is_multiple_of(point_size) {
+        return Err(DriverClientError::InputSizeMismatch {
+            expected: data.len().next_multiple_of(point_size),
+            actual: data.len(),
+        });
+    }
+
+    let batches: Vec<Result<Vec<C>>> = data
+        .par_chunks(DECOMPRESS_BATCH * point_size)
+        .enumerate()
+        .map(|(batch, data)| {
+            data.chunks_exact(point_size)
+                .enumerate()
+                .map(|(i, bytes)| {
+                    decompress(bytes, compression).ok_or(
+                        DriverClientError::InvalidCompressedPoint {
+                            index: batch * DECOMPRESS_BATCH + i,
+                        },
+                    )
+                })
+                .collect()
+        })
+        .collect();
+    let mut points = Vec::with_capacity(data.len() / point_size);
+    for batch in batches {
+        points.extend(batch?);
+    }
+    Ok(points)
+}
+
+/// Decompresses points and serializes them in the layout of [`precompute_bases`],
+/// expanded with `precompute_factor`.
+pub fn decompress_bases<C: MSMCurve>(
+    data: &[u8],
+    compression: PointCompression,
+    precompute_factor: u32,
+) -> Result<Vec<u8>> {
+    precompute_bases(
+        &decompress_points::<C>(data, compression)?,
+        precompute_factor,
+    )
+}
+
+/// Decompresses a file of points into a file of bases in the layout of [`precompute_bases`],
+/// which can be used with `set_data_from_files` and `load_data_to_hbm_from_file`.
+///
+/// The input is memory-mapped and converted by windows of [`FILE_WINDOW_ELEMENTS`] points.
+/// Indices of invalid points are counted from the start of the file.
+/// Returns the number of points.
+pub fn decompress_file<C: MSMCurve, P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    compression: PointCompression,
+    precompute_factor: u32,
+) -> Result<usize> {
+    let input = MappedFile::open(input)?;
+    let point_size = compression.point_size::<C>();
+    let window = FILE_WINDOW_ELEMENTS * point_size;
+    let mut writer = BufWriter::new(File::create(output)?);
+    for start in (0..input.len()).step_by(window) {
+        let end = (start + window).min(input.len());
+        let bases = decompress_bases::<C>(&input[start..end], compression, precompute_factor)
+            .map_err(|e| match e {
+                DriverClientError::InvalidCompressedPoint { index } => {
+                    DriverClientError::InvalidCompressedPoint {
+                        index: start / point_size + index,
+                    }
+                }
+                e => e,
+            })?;
+        writer.write_all(&bases)?;
+        input.release(start, end);
+    }
+    writer.flush()?;
+    Ok(input.len() / point_size)
+}
+
+fn decompress<C: MSMCurve>(bytes: &[u8], compression: PointCompression) -> Option<C> {
+    match compression {
+        PointCompression::Arkworks => C::deserialize(bytes).ok(),
+        PointCompression::Zcash => {
+            let flags = bytes[0];
+            if flags & ZCASH_COMPRESSED == 0 {
+                return None;
+            }
+            let mut x = bytes.to_vec();
+            x[0] &= !(ZCASH_COMPRESSED | ZCASH_INFINITY | ZCASH_Y_SIGN);
+            if flags & ZCASH_INFINITY != 0 {
+                // The point at infinity has no other bits set.
+                let is_empty = flags & ZCASH_Y_SIGN == 0 && x.iter().all(|b| *b == 0);
+                return is_empty.then(C::zero);
+            }
+            // Big-endian `x` reversed is little-endian, for G2 it also swaps `c1` and `c0`.
+            x.reverse();
+            C::from_x(&x, flags & ZCASH_Y_SIGN != 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_381::{G1Affine, G2Affine};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+
+    /// Compressed `CanonicalSerialize` of a point, the trait is in scope through `AffineCurve`.
+    fn arkworks<C: MSMCurve>(point: &C) -> Vec<u8> {
+        let mut out = vec![];
+        point.serialize(&mut out).unwrap();
+        out
+    }
+
+    /// Encodes a point in the zcash format.
+    fn zcash<C: MSMCurve>(point: &C) -> Vec<u8> {
+        let mut base = vec![0; C::base_size()];
+        point.write_base(&mut base);
+        let mut x = base[..C::base_size() / 2].to_vec();
+        x.reverse();
+        x[0] |= ZCASH_COMPRESSED;
+        let neg = -*point;
+        let mut neg_base = vec![0; C::base_size()];
+        neg.write_base(&mut neg_base);
+        // `y` is the larger one if it is not smaller than `-y`, compared from the top byte.
+        let mut y = base[C::base_size() / 2..].to_vec();
+        let mut neg_y = neg_base[C::base_size() / 2..].to_vec();
+        y.reverse();
+        neg_y.reverse();
+        if y > neg_y {
+            x[0] |= ZCASH_Y_SIGN;
+        }
+        x
+    }
+
+    #[test]
+    fn decompress_arkworks_and_zcash_points() {
+        let g = G1Affine::prime_subgroup_generator();
+        let points: Vec<G1Affine> = (1..=5u64).map(|i| g.mul(i).into_affine()).collect();
+
+        let compressed: Vec<u8> = points.iter().flat_map(arkworks).collect();
+        assert_eq!(compressed.len(), 5 * 48);
+        assert_eq!(
+            decompress_points::<G1Affine>(&compressed, PointCompression::Arkworks).unwrap(),
+            points
+        );
+
+        let compressed: Vec<u8> = points.iter().flat_map(zcash).collect();
+        assert_eq!(
+            decompress_points::<G1Affine>(&compressed, PointCompression::Zcash).unwrap(),
+            points
+        );
+        assert_eq!(
+            decompress_bases::<G1Affine>(&compressed, PointCompression::Zcash, 1).unwrap(),
+            precompute_bases(&points, 1).unwrap()
+        );
+
+        let g2 = G2Affine::prime_subgroup_generator().mul(3u64).into_affine();
+        assert_eq!(
+            decompress_points::<G2Affine>(&zcash(&g2), PointCompression::Zcash).unwrap(),
+            [g2]
+        );
+
+        let mut invalid = compressed.clone();
+        invalid[3 * 48] &= !ZCASH_COMPRESSED;
+        assert!(matches!(
+            decompress_points::<G1Affine>(&invalid, PointCompression::Zcash),
+            Err(DriverClientError::InvalidCompressedPoint { index: 3 })
+        ));
+        assert!(matches!(
+            decompress_points::<G1Affine>(&compressed[1..], PointCompression::Zcash),
+            Err(DriverClientError::InputSizeMismatch { .. })
+        ));
+        assert!(matches!(
+            decompress_points::<ark_bn254::G1Affine>(&[0; 32], PointCompression::Zcash),
+            Err(DriverClientError::UnsupportedCompression { .. })
+        ));
+    }
+}
@@ -0,0 +1,274 @@
+//! MSM on the host.
+//!
+//! [`CpuMSM`] is a fallback for hosts without a working card. It reads the same input layout
+//! as the MSM core, including precomputed multiples which are skipped, and runs Pippenger's
+//! algorithm of arkworks on chunks of the input in parallel. Inputs which read bases from HBM
+//! are computed over host copies of the bases, see [`CpuMSM::with_resident_bases`].
+use super::{msm_api::*, msm_backend::*, msm_cfg::*, msm_curve::*, msm_precompute::*};
+use crate::error::*;
+
+use ark_ec::msm::VariableBaseMSM;
+use ark_ff::{FromBytes, PrimeField, Zero};
+use rayon::prelude::*;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+/// Host copy of bases which are resident in HBM, e.g. the [`PrecomputedBases`] a set was loaded from.
+pub type HostBases = Arc<dyn AsRef<[u8]> + Send + Sync>;
+
+/// A CPU backend of MSM for the curve, the group and the precompute factor of [`MSMInit`].
+///
+/// # Example
+///
+/// ```rust
+/// use ark_bn254::{Fr, G1Affine};
+/// use ark_ec::AffineCurve;
+/// use ark_ff::{BigInteger, PrimeField};
+/// use ingo_blaze::ingo_msm::*;
+///
+/// let cpu = CpuMSM::new(MSMInit {
+///     mem_type: PointMemoryType::DMA,
+///     precompute_factor: PRECOMPUTE_FACTOR_BASE,
+///     curve: Curve::BN254,
+///     group: MSMGroup::G1,
+///     tuning: MSMTuning::default(),
+/// })
+/// .unwrap();
+/// let bases = vec![G1Affine::prime_subgroup_generator(); 2];
+/// let scalars: Vec<u8> = [Fr::from(2u64), Fr::from(3u64)]
+///     .iter()
+///     .flat_map(|s| s.into_repr().to_bytes_le())
+///     .collect();
+/// let result = cpu
+///     .run(MSMInput {
+///         points: Some(&precompute_bases(&bases, PRECOMPUTE_FACTOR_BASE).unwrap()),
+///         scalars: &scalars,
+///         params: MSMParams {
+///             nof_elements: 2,
+///             hbm_point_addr: None,
+///             tuning: None,
+///         },
+///     })
+///     .unwrap();
+/// let expected = G1Affine::prime_subgroup_generator().mul(5u64);
+/// assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+/// ```
+pub struct CpuMSM {
+    curve: Curve,
+    group: MSMGroup,
+    mem_type: PointMemoryType,
+    precompute_factor: u32,
+    msm_cfg: MSMConfig,
+    /// Host copies of bases in HBM by their start address.
+    resident: Vec<(u64, HostBases)>,
+    /// Labels of results, counted like task labels of the core.
+    next_label: AtomicU32,
+}
+
+impl CpuMSM {
+    /// Creates a CPU backend, the tuning of `init` is not used. With the memory type
+    /// [`PointMemoryType::HBM`] bases are read from host copies, see [`CpuMSM::with_resident_bases`].
+    /// The precompute factor is validated like in [`MSMClient::try_new`].
+    pub fn new(init: MSMInit) -> Result<Self> {
+        let msm_cfg = MSMConfig::msm_cfg(init.curve, init.group, init.mem_type);
+        check_precompute_factor(msm_cfg.scalar_size * 8, init.precompute_factor)?;
+        Ok(CpuMSM {
+            curve: init.curve,
+            group: init.group,
+            mem_type: init.mem_type,
+            precompute_factor: init.precompute_factor,
+            msm_cfg,
+            resident: Vec::new(),
+            next_label: AtomicU32::new(0),
+        })
+    }
+
+    /// Registers a host copy of bases which are loaded into HBM at `addr`, in the layout of
+    /// [`MSMInput::points`]. Inputs without points whose [`MSMParams::hbm_point_addr`] falls
+    /// into the region are computed over these bases, other inputs without points are rejected
+    /// with [`DriverClientError::MissingPoints`].
+    pub fn with_resident_bases(mut self, addr: u64, points: HostBases) -> Self {
+        self.resident.push((addr, points));
+        self
+    }
+
+    /// Points of the input, or host copies of the bases in HBM the input reads.
+    pub(super) fn input_points<'a>(&'a self, input: &MSMInput<'a>) -> Option<&'a [u8]> {
+        if input.points.is_some() {
+            return input.points;
+        }
+        let (addr, _) = input.params.hbm_point_addr?;
+        self.resident.iter().find_map(|(start, points)| {
+            let points = points.as_ref().as_ref();
+            let offset = addr.checked_sub(*start)? as usize;
+            (offset < points.len()).then(|| &points[offset..])
+        })
+    }
+
+    fn run_curve<C: MSMCurve>(&self, input: MSMInput) -> Result<Vec<u8>> {
+        let nof_elements = input.params.nof_elements as usize;
+        let points = self
+            .input_points(&input)
+            .ok_or(DriverClientError::MissingPoints)?;
+        let scalar_size = self.msm_cfg.scalar_size;
+        let element_size = C::base_size() * self.precompute_factor as usize;
+        check_input_size(input.scalars.len(), nof_elements * scalar_size)?;
+        check_input_size(points.len(), nof_elements * element_size)?;
+
+        // Only the first point of an element is the base itself.
+        let bases = points[..nof_elements * element_size]
+            .par_chunks(element_size)
+            .enumerate()
+            .map(|(index, element)| {
+                C::read_base(&element[..C::base_size()])
+                    .ok_or(DriverClientError::InvalidBase { index })
+            })
+            .collect::<Result<Vec<C>>>()?;
+        let scalars = input.scalars[..nof_elements * scalar_size]
+            .par_chunks(scalar_size)
+            .enumerate()
+            .map(|(index, scalar)| {
+                <C::ScalarField as PrimeField>::BigInt::read(scalar)
+                    .ok()
+                    .filter(|repr| C::ScalarField::from_repr(*repr).is_some())
+                    .ok_or(DriverClientError::ScalarOutOfRange { index })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let chunk = nof_elements.div_ceil(rayon::current_num_threads()).max(1);
+        let result = bases
+            .par_chunks(chunk)
+            .zip(scalars.par_chunks(chunk))
+            .map(|(bases, scalars)| VariableBaseMSM::multi_scalar_mul(bases, scalars))
+            .reduce(C::Projective::zero, |a, b| a + b);
+        Ok(C::encode_result(&result))
+    }
+}
+
+impl MSMBackend for CpuMSM {
+    fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    fn group(&self) -> MSMGroup {
+        self.group
+    }
+
+    fn mem_type(&self) -> PointMemoryType {
+        self.mem_type
+    }
+
+    fn precompute_factor(&self) -> u32 {
+        self.precompute_factor
+    }
+
+    fn run(&self, input: MSMInput) -> Result<MSMResult> {
+        let result = match (self.curve, self.group) {
+            (Curve::BLS377, MSMGroup::G1) => self.run_curve::<ark_bls12_377::G1Affine>(input),
+            (Curve::BLS381, MSMGroup::G1) => self.run_curve::<ark_bls12_381::G1Affine>(input),
+            (Curve::BN254, MSMGroup::G1) => self.run_curve::<ark_bn254::G1Affine>(input),
+            (Curve::BLS377, MSMGroup::G2) => self.run_curve::<ark_bls12_377::G2Affine>(input),
+            (Curve::BLS381, MSMGroup::G2) => self.run_curve::<ark_bls12_381::G2Affine>(input),
+            (Curve::BN254, MSMGroup::G2) => self.run_curve::<ark_bn254::G2Affine>(input),
+        }?;
+        Ok(MSMResult {
+            result,
+            result_label: self.next_label.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::{Fr, G1Affine};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
+    use ark_ff::BigInteger;
+
+    #[test]
+    fn cpu_msm_with_precomputed_bases() {
+        let init = MSMInit {
+            mem_type: PointMemoryType::HBM,
+            precompute_factor: PRECOMPUTE_FACTOR,
+            curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        };
+        let cpu = CpuMSM::new(init).unwrap();
+        let g = G1Affine::prime_subgroup_generator();
+        let mut bases: Vec<G1Affine> = (1..=100u64).map(|i| g.mul(i).into_affine()).collect();
+        bases[7] = G1Affine::zero();
+        let scalars: Vec<Fr> = (0..100u64).map(|i| Fr::from(i * i + 1)).collect();
+        let expected = bases
+            .iter()
+            .zip(scalars.iter())
+            .map(|(b, s)| b.mul(*s))
+            .fold(ark_bls12_377::G1Projective::zero(), |a, b| a + b);
+
+        let points = precompute_bases(&bases, PRECOMPUTE_FACTOR).unwrap();
+        let mut scalar_bytes: Vec<u8> = scalars
+            .iter()
+            .flat_map(|s| s.into_repr().to_bytes_le())
+            .collect();
+        let params = MSMParams {
+            nof_elements: 100,
+            hbm_point_addr: None,
+            tuning: None,
+        };
+        let result = cpu
+            .run(MSMInput {
+                points: Some(&points),
+                scalars: &scalar_bytes,
+                params,
+            })
+            .unwrap();
+        assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+
+        scalar_bytes[3 * 32 + 31] = 0xFF;
+        assert!(matches!(
+            cpu.run(MSMInput {
+                points: Some(&points),
+                scalars: &scalar_bytes,
+                params,
+            }),
+            Err(DriverClientError::ScalarOutOfRange { index: 3 })
+        ));
+
+        // Bases in HBM are read from their host copy.
+        let element_size = 2 * 48 * PRECOMPUTE_FACTOR as usize;
+        let hbm_input = MSMInput {
+            points: None,
+            scalars: &scalar_bytes[10 * 32..30 * 32],
+            params: MSMParams {
+                nof_elements: 20,
+                hbm_point_addr: Some((0x1000 + 10 * element_size as u64, 0)),
+                tuning: None,
+            },
+        };
+        assert!(matches!(
+            cpu.run(hbm_input),
+            Err(DriverClientError::MissingPoints)
+        ));
+        let cpu = CpuMSM::new(init)
+            .unwrap()
+            .with_resident_bases(0x1000, Arc::new(points.clone()));
+        let expected = bases[10..30]
+            .iter()
+            .zip(scalars[10..30].iter())
+            .map(|(b, s)| b.mul(*s))
+            .fold(ark_bls12_377::G1Projective::zero(), |a, b| a + b);
+        let result = cpu.run(hbm_input).unwrap();
+        assert_eq!(result.to_projective::<G1Affine>().unwrap(), expected);
+
+        // 253 bits of BLS12-377 scalars are padded to 256, which doesn't split into 3 windows.
+        assert!(matches!(
+            CpuMSM::new(MSMInit {
+                precompute_factor: 3,
+                ..init
+            }),
+            Err(DriverClientError::InvalidPrecomputeFactor { factor: 3 })
+        ));
+    }
+}
@@ -0,0 +1,506 @@
+//! Typed MSM on top of arkworks curves.
+//!
+//! [`MSMClient::msm`] takes affine bases and scalar field elements, serializes them into the layout
+//! expected by the MSM core, expands bases with precomputed multiples and decodes the result point.
+//! Points of G1 and G2 are supported, G2 coordinates are serialized as `c0` followed by `c1`.
+use super::{msm_api::*, msm_cfg::*, msm_precompute::*};
+use crate::error::*;
+
+use ark_ec::{
+    short_weierstrass_jacobian::GroupAffine, AffineCurve, ProjectiveCurve, SWModelParameters,
+};
+use ark_ff::{
+    BigInteger, Field, Fp256, Fp256Parameters, Fp384, Fp384Parameters, FromBytes, One, PrimeField,
+    QuadExtField, QuadExtParameters, Zero,
+};
+
+/// Short Weierstrass curve parameters of a point group supported by the MSM core.
+pub trait MSMCurveParameters: SWModelParameters {
+    /// The curve of the MSM image which works with the group.
+    const CURVE: Curve;
+    const GROUP: MSMGroup;
+}
+
+impl MSMCurveParameters for ark_bls12_377::g1::Parameters {
+    const CURVE: Curve = Curve::BLS377;
+    const GROUP: MSMGroup = MSMGroup::G1;
+}
+
+impl MSMCurveParameters for ark_bls12_381::g1::Parameters {
+    const CURVE: Curve = Curve::BLS381;
+    const GROUP: MSMGroup = MSMGroup::G1;
+}
+
+impl MSMCurveParameters for ark_bn254::g1::Parameters {
+    const CURVE: Curve = Curve::BN254;
+    const GROUP: MSMGroup = MSMGroup::G1;
+}
+
+impl MSMCurveParameters for ark_bls12_377::g2::Parameters {
+    const CURVE: Curve = Curve::BLS377;
+    const GROUP: MSMGroup = MSMGroup::G2;
+}
+
+impl MSMCurveParameters for ark_bls12_381::g2::Parameters {
+    const CURVE: Curve = Curve::BLS381;
+    const GROUP: MSMGroup = MSMGroup::G2;
+}
+
+impl MSMCurveParameters for ark_bn254::g2::Parameters {
+    const CURVE: Curve = Curve::BN254;
+    const GROUP: MSMGroup = MSMGroup::G2;
+}
+
+/// A field of point coordinates of the MSM core.
+pub trait MSMField: Field {
+    /// The size in bytes of a serialized element.
+    fn byte_size() -> usize;
+
+    /// Writes the element into `out` of [`MSMField::byte_size`] bytes in little-endian.
+    /// An element of a quadratic extension is written as `c0` followed by `c1`.
+    fn write_le(&self, out: &mut [u8]);
+
+    /// Reads an element written by [`MSMField::write_le`],
+    /// returns `None` if it is not a canonical field element.
+    fn read_le(bytes: &[u8]) -> Option<Self>;
+
+    /// Reads an element in the layout of [`MSMField::write_le`] and reduces it modulo
+    /// the field modulus, e.g. a lazily reduced coordinate of a result of the core.
+    fn read_le_reduced(bytes: &[u8]) -> Self;
+}
+
+impl<P: Fp256Parameters> MSMField for Fp256<P> {
+    fn byte_size() -> usize {
+        coordinate_size::<Self>()
+    }
+
+    fn write_le(&self, out: &mut [u8]) {
+        out.copy_from_slice(&self.into_repr().to_bytes_le());
+    }
+
+    fn read_le(bytes: &[u8]) -> Option<Self> {
+        read_coordinate(bytes)
+    }
+
+    fn read_le_reduced(bytes: &[u8]) -> Self {
+        Self::from_le_bytes_mod_order(bytes)
+    }
+}
+
+impl<P: Fp384Parameters> MSMField for Fp384<P> {
+    fn byte_size() -> usize {
+        coordinate_size::<Self>()
+    }
+
+    fn write_le(&self, out: &mut [u8]) {
+        out.copy_from_slice(&self.into_repr().to_bytes_le());
+    }
+
+    fn read_le(bytes: &[u8]) -> Option<Self> {
+        read_coordinate(bytes)
+    }
+
+    fn read_le_reduced(bytes: &[u8]) -> Self {
+        Self::from_le_bytes_mod_order(bytes)
+    }
+}
+
+impl<P: QuadExtParameters> MSMField for QuadExtField<P>
+where
+    P::BaseField: MSMField,
+{
+    fn byte_size() -> usize {
+        2 * P::BaseField::byte_size()
+    }
+
+    fn write_le(&self, out: &mut [u8]) {
+        let (c0, c1) = out.split_at_mut(out.len() / 2);
+        self.c0.write_le(c0);
+        self.c1.write_le(c1);
+    }
+
+    fn read_le(bytes: &[u8]) -> Option<Self> {
+        let (c0, c1) = bytes.split_at(bytes.len() / 2);
+        Some(QuadExtField::new(
+            P::BaseField::read_le(c0)?,
+            P::BaseField::read_le(c1)?,
+        ))
+    }
+
+    fn read_le_reduced(bytes: &[u8]) -> Self {
+        let (c0, c1) = bytes.split_at(bytes.len() / 2);
+        QuadExtField::new(
+            P::BaseField::read_le_reduced(c0),
+            P::BaseField::read_le_reduced(c1),
+        )
+    }
+}
+
+/// An affine curve point type which can be used as a base of the MSM core.
+pub trait MSMCurve: AffineCurve {
+    /// The curve of the MSM image which works with the point type.
+    const CURVE: Curve;
+    /// The group of the MSM image which works with the point type.
+    const GROUP: MSMGroup;
+
+    /// Writes the point into `out` as little-endian `x` followed by little-endian `y`.
+    /// Each coordinate takes a half of `out`.
+    fn write_base(&self, out: &mut [u8]);
+
+    /// The size in bytes of a base written by [`MSMCurve::write_base`].
+    fn base_size() -> usize;
+
+    /// Reads a base written by [`MSMCurve::write_base`], the encoding of the point at infinity
+    /// (`x = 0`, `y = 1`) and all zero bytes are read as the point at infinity.
+    /// Returns `None` if a coordinate is not a field element or the point is not on the curve.
+    fn read_base(bytes: &[u8]) -> Option<Self>;
+
+    /// Decodes a result of the MSM core: projective `z`, `y` and `x` coordinates in little-endian.
+    ///
+    /// Coordinates are reduced modulo the field modulus. Returns
+    /// [`DriverClientError::MalformedResult`] if the result has a wrong size, or the point
+    /// is not on the curve or not in the prime order subgroup.
+    /// `z = 0` is decoded as the point at infinity.
+    fn decode_result(result: &[u8]) -> Result<Self::Projective>;
+
+    /// Encodes a point into the result format of the MSM core,
+    /// the point at infinity is encoded with `z = 0`.
+    fn encode_result(point: &Self::Projective) -> Vec<u8>;
+
+    /// Recovers a point from little-endian `x`, `greatest` selects the larger of the two `y`.
+    /// Returns `None` if `x` is not a field element or there is no point with this `x`
+    /// in the prime order subgroup.
+    fn from_x(x: &[u8], greatest: bool) -> Option<Self>;
+}
+
+impl<P: MSMCurveParameters> MSMCurve for GroupAffine<P>
+where
+    P::BaseField: MSMField,
+{
+    const CURVE: Curve = P::CURVE;
+    const GROUP: MSMGroup = P::GROUP;
+
+    fn write_base(&self, out: &mut [u8]) {
+        let (x, y) = out.split_at_mut(out.len() / 2);
+        self.x.write_le(x);
+        self.y.write_le(y);
+    }
+
+    fn base_size() -> usize {
+        2 * P::BaseField::byte_size()
+    }
+
+    fn read_base(bytes: &[u8]) -> Option<Self> {
+        let (x, y) = bytes.split_at(bytes.len() / 2);
+        let (x, y) = (P::BaseField::read_le(x)?, P::BaseField::read_le(y)?);
+        if x.is_zero() && (y.is_zero() || y.is_one()) {
+            return Some(Self::zero());
+        }
+        let point = GroupAffine::<P>::new(x, y, false);
+        point.is_on_curve().then_some(point)
+    }
+
+    fn decode_result(result: &[u8]) -> Result<Self::Projective> {
+        let size = P::BaseField::byte_size();
+        if result.len() != 3 * size {
+            return Err(malformed(format!(
+                "expected {} bytes, got {}",
+                3 * size,
+                result.len()
+            )));
+        }
+        // The core may return coordinates which are not fully reduced,
+        // a wrong result is caught by the curve and subgroup checks.
+        let z = P::BaseField::read_le_reduced(&result[0..size]);
+        let y = P::BaseField::read_le_reduced(&result[size..2 * size]);
+        let x = P::BaseField::read_le_reduced(&result[2 * size..3 * size]);
+
+        let z_inv = match z.inverse() {
+            Some(z_inv) => z_inv,
+            None => return Ok(Self::Projective::zero()),
+        };
+        let point = GroupAffine::<P>::new(x * z_inv, y * z_inv, false);
+        if !point.is_on_curve() {
+            return Err(malformed("point is not on the curve".to_string()));
+        }
+        if !point.is_in_correct_subgroup_assuming_on_curve() {
+            return Err(malformed(
+                "point is not in the prime order subgroup".to_string(),
+            ));
+        }
+        Ok(point.into_projective())
+    }
+
+    fn encode_result(point: &Self::Projective) -> Vec<u8> {
+        let point = point.into_affine();
+        let (z, y, x) = if point.is_zero() {
+            (
+                P::BaseField::zero(),
+                P::BaseField::one(),
+                P::BaseField::zero(),
+            )
+        } else {
+            (P::BaseField::one(), point.y, point.x)
+        };
+        let size = P::BaseField::byte_size();
+        let mut out = vec![0; 3 * size];
+        for (c, out) in [z, y, x].iter().zip(out.chunks_exact_mut(size)) {
+            c.write_le(out);
+        }
+        out
+    }
+
+    fn from_x(x: &[u8], greatest: bool) -> Option<Self> {
+        let point = GroupAffine::<P>::get_point_from_x(P::BaseField::read_le(x)?, greatest)?;
+        point
+            .is_in_correct_subgroup_assuming_on_curve()
+            .then_some(point)
+    }
+}
+
+/// Adds up results of several MSM tasks of the curve and the group
+/// and encodes the sum in the same format.
+pub(super) fn combine_results(
+    curve: Curve,
+    group: MSMGroup,
+    results: &[MSMResult],
+) -> Result<Vec<u8>> {
+    fn combine<C: MSMCurve>(results: &[MSMResult]) -> Result<Vec<u8>> {
+        let mut sum = C::Projective::zero();
+        for result in results {
+            sum += &result.to_projective::<C>()?;
+        }
+        Ok(C::encode_result(&sum))
+    }
+
+    match (curve, group) {
+        (Curve::BLS377, MSMGroup::G1) => combine::<ark_bls12_377::G1Affine>(results),
+        (Curve::BLS381, MSMGroup::G1) => combine::<ark_bls12_381::G1Affine>(results),
+        (Curve::BN254, MSMGroup::G1) => combine::<ark_bn254::G1Affine>(results),
+        (Curve::BLS377, MSMGroup::G2) => combine::<ark_bls12_377::G2Affine>(results),
+        (Curve::BLS381, MSMGroup::G2) => combine::<ark_bls12_381::G2Affine>(results),
+        (Curve::BN254, MSMGroup::G2) => combine::<ark_bn254::G2Affine>(results),
+    }
+}
+
+/// The size in bytes of a serialized field element, it is a whole number of 64-bit limbs.
+pub(super) fn coordinate_size<F: PrimeField>() -> usize {
+    (F::size_in_bits()).div_ceil(64) * 8
+}
+
+fn read_coordinate<F: PrimeField>(bytes: &[u8]) -> Option<F> {
+    F::BigInt::read(bytes).ok().and_then(F::from_repr)
+}
+
+fn malformed(reason: String) -> DriverClientError {
+    DriverClientError::MalformedResult { reason }
+}
+
+impl MSMResult {
+    /// Decodes the result into a projective point of the curve `C`, see [`MSMCurve::decode_result`].
+    pub fn to_projective<C: MSMCurve>(&self) -> Result<C::Projective> {
+        C::decode_result(&self.result)
+    }
+
+    /// Decodes the result into an affine point of the curve `C`, see [`MSMCurve::decode_result`].
+    pub fn to_affine<C: MSMCurve>(&self) -> Result<C> {
+        Ok(self.to_projective::<C>()?.into_affine())
+    }
+
+    /// Checks that the result is a valid point of the given `curve` and `group`.
+    pub fn validate(&self, curve: Curve, group: MSMGroup) -> Result<()> {
+        combine_results(curve, group, std::slice::from_ref(self)).map(|_| ())
+    }
+}
+
+impl MSMClient {
+    /// Computes `sum(scalars[i] * bases[i])` on the MSM core.
+    ///
+    /// Bases and scalars are serialized into pooled DMA buffers, bases are expanded with
+    /// precomputed multiples if the client was created with precompute enabled.
+    /// In HBM mode bases are loaded to a free region of HBM which is released after the task,
+    /// resident base sets are not touched.
+    /// MSMs larger than the maximum task size are split, see [`MSMClient::run`].
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use ark_bls12_377::{Fr, G1Affine};
+    /// use ark_ec::AffineCurve;
+    /// use ark_std::UniformRand;
+    /// use ingo_blaze::{driver_client::*, ingo_msm::*};
+    ///
+    /// let dclient = DriverClient::new("0", DriverConfig::driver_client_cfg(CardType::C1100));
+    /// let client = MSMClient::new(
+    ///     MSMInit {
+    ///         mem_type: PointMemoryType::DMA,
+    ///         precompute_factor: PRECOMPUTE_FACTOR_BASE,
+    ///         curve: Curve::BLS377,
+    ///         group: MSMGroup::G1,
+    ///         tuning: MSMTuning::default(),
+    ///     },
+    ///     dclient,
+    /// );
+    /// let mut rng = ark_std::rand::thread_rng();
+    /// let bases: Vec<G1Affine> = (0..8).map(|_| G1Affine::prime_subgroup_generator()).collect();
+    /// let scalars: Vec<Fr> = (0..8).map(|_| Fr::rand(&mut rng)).collect();
+    /// let result = client.msm(&bases, &scalars).unwrap();
+    /// ```
+    pub fn msm<C: MSMCurve>(
+        &self,
+        bases: &[C],
+        scalars: &[C::ScalarField],
+    ) -> Result<C::Projective> {
+        if bases.len() != scalars.len() {
+            return Err(DriverClientError::LengthMismatch {
+                bases: bases.len(),
+                scalars: scalars.len(),
+            });
+        }
+        if C::CURVE != self.curve {
+            return Err(DriverClientError::CurveMismatch {
+                client: format!("{:?}", self.curve),
+                requested: format!("{:?}", C::CURVE),
+            });
+        }
+        if C::GROUP != self.group {
+            return Err(DriverClientError::GroupMismatch {
+                client: format!("{:?}", self.group),
+                requested: format!("{:?}", C::GROUP),
+            });
+        }
+        if bases.is_empty() {
+            return Ok(C::Projective::zero());
+        }
+
+        let mut points = self.points_buffer(bases.len())?;
+        precompute_bases_into(bases, self.precompute_factor, &mut points)?;
+        let mut scalars_buf = self.scalars_buffer(scalars.len())?;
+        let scalar_size = self.msm_cfg.scalar_size;
+        for (s, out) in scalars
+            .iter()
+            .zip(scalars_buf.chunks_exact_mut(scalar_size))
+        {
+            let bytes = s.into_repr().to_bytes_le();
+            out[..bytes.len()].copy_from_slice(&bytes);
+        }
+
+        let run = |points, hbm_point_addr| {
+            self.run(MSMInput {
+                points,
+                scalars: &scalars_buf,
+                params: MSMParams {
+                    nof_elements: bases.len() as u32,
+                    hbm_point_addr,
+                    tuning: None,
+                },
+            })
+        };
+        let result = match self.mem_type {
+            PointMemoryType::DMA => run(Some(&points), None)?,
+            // The scratch region is taken in the allocator, so the points are written to it directly.
+            PointMemoryType::HBM => self.with_scratch_region(points.len(), |addr| {
+                self.write_hbm_points(&points, addr, 0)?;
+                run(None, Some((addr, 0)))
+            })?,
+        };
+        result.to_projective::<C>()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::FpParameters;
+
+    #[test]
+    fn decode_and_validate_result() {
+        let point = ark_bls12_377::G1Affine::prime_subgroup_generator()
+            .mul(7u64)
+            .into_affine();
+        let z = ark_bls12_377::Fq::from(5u64);
+        let mut result = vec![];
+        result.extend(z.into_repr().to_bytes_le());
+        result.extend((point.y * z).into_repr().to_bytes_le());
+        result.extend((point.x * z).into_repr().to_bytes_le());
+        assert_eq!(
+            ark_bls12_377::G1Affine::decode_result(&result)
+                .unwrap()
+                .into_affine(),
+            point
+        );
+
+        // The core may return `z + p` in place of `z`.
+        let mut unreduced = result.clone();
+        let mut z_plus_p = z.into_repr();
+        z_plus_p.add_nocarry(&<ark_bls12_377::FqParameters as FpParameters>::MODULUS);
+        unreduced[..48].copy_from_slice(&z_plus_p.to_bytes_le());
+        assert_eq!(
+            ark_bls12_377::G1Affine::decode_result(&unreduced)
+                .unwrap()
+                .into_affine(),
+            point
+        );
+
+        result[60] ^= 1;
+        assert!(matches!(
+            ark_bls12_377::G1Affine::decode_result(&result),
+            Err(DriverClientError::MalformedResult { .. })
+        ));
+        assert!(ark_bls12_377::G1Affine::decode_result(&result[..96]).is_err());
+
+        let zero = [
+            ark_bn254::Fq::zero(),
+            ark_bn254::Fq::one(),
+            ark_bn254::Fq::zero(),
+        ]
+        .iter()
+        .flat_map(|c| c.into_repr().to_bytes_le())
+        .collect::<Vec<u8>>();
+        assert!(ark_bn254::G1Affine::decode_result(&zero).unwrap().is_zero());
+    }
+
+    #[test]
+    fn g2_bases_and_results() {
+        let point = ark_bls12_381::G2Affine::prime_subgroup_generator()
+            .mul(3u64)
+            .into_affine();
+        let mut base = vec![0; ark_bls12_381::G2Affine::base_size()];
+        point.write_base(&mut base);
+        assert_eq!(base.len(), 192);
+        assert_eq!(base[..48], point.x.c0.into_repr().to_bytes_le());
+        assert_eq!(base[48..96], point.x.c1.into_repr().to_bytes_le());
+        assert_eq!(base[144..], point.y.c1.into_repr().to_bytes_le());
+
+        let result = ark_bls12_381::G2Affine::encode_result(&point.into_projective());
+        assert_eq!(
+            result.len(),
+            MSMConfig::msm_cfg(Curve::BLS381, MSMGroup::G2, PointMemoryType::DMA).result_point_size
+        );
+        assert_eq!(
+            ark_bls12_381::G2Affine::decode_result(&result).unwrap(),
+            point.into_projective()
+        );
+        let result = MSMResult {
+            result,
+            result_label: 0,
+        };
+        assert!(result.validate(Curve::BLS381, MSMGroup::G2).is_ok());
+        assert!(result.validate(Curve::BLS381, MSMGroup::G1).is_err());
+    }
+
+    #[test]
+    fn combine_encoded_results() {
+        let g = ark_bn254::G1Affine::prime_subgroup_generator();
+        let results =
+            [g.mul(2u64), g.mul(5u64), ark_bn254::G1Projective::zero()].map(|p| MSMResult {
+                result: ark_bn254::G1Affine::encode_result(&p),
+                result_label: 0,
+            });
+        let sum = combine_results(Curve::BN254, MSMGroup::G1, &results).unwrap();
+        assert_eq!(
+            ark_bn254::G1Affine::decode_result(&sum).unwrap(),
+            g.mul(7u64)
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Filtering of trivial scalars before an MSM is sent to the device.
+//!
+//! Witness vectors are often dominated by zeros and ones. Pairs with a zero scalar don't change
+//! the result and are dropped, bases with a scalar of one are added up on the host,
+//! so only the remaining pairs are serialized and sent by DMA.
+use super::{msm_api::*, msm_curve::*};
+use crate::error::*;
+
+use ark_ec::ProjectiveCurve;
+use ark_ff::{One, Zero};
+
+/// What was filtered out of an MSM and how much DMA it saved.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MSMFilterStats {
+    /// Number of elements of the original MSM.
+    pub nof_elements: usize,
+    /// Pairs dropped because of a zero scalar.
+    pub nof_zeros: usize,
+    /// Bases with a scalar of one which were added up on the host.
+    pub nof_ones: usize,
+    /// Bytes of bases (including precomputed multiples) and scalars which were not sent.
+    pub saved_bytes: usize,
+}
+
+impl MSMFilterStats {
+    /// Number of elements sent to the device.
+    pub fn nof_sent(&self) -> usize {
+        self.nof_elements - self.nof_zeros - self.nof_ones
+    }
+
+    /// Percentage of elements which were not sent to the device.
+    pub fn saved_percentage(&self) -> f64 {
+        if self.nof_elements == 0 {
+            return 0.0;
+        }
+        (self.nof_zeros + self.nof_ones) as f64 * 100.0 / self.nof_elements as f64
+    }
+}
+
+/// An MSM with trivial scalars filtered out, see [`filter_scalars`].
+pub struct FilteredMSM<C: MSMCurve> {
+    /// Bases of pairs with a scalar other than zero and one.
+    pub bases: Vec<C>,
+    pub scalars: Vec<C::ScalarField>,
+    /// Sum of bases with a scalar of one.
+    pub ones_sum: C::Projective,
+    pub stats: MSMFilterStats,
+}
+
+/// Drops pairs with a zero scalar and adds up bases with a scalar of one.
+/// `saved_bytes` of the stats is left zero, it depends on the layout of the client.
+/// Returns [`DriverClientError::LengthMismatch`] if there is not one scalar per base.
+pub fn filter_scalars<C: MSMCurve>(
+    bases: &[C],
+    scalars: &[C::ScalarField],
+) -> Result<FilteredMSM<C>> {
+    if bases.len() != scalars.len() {
+        return Err(DriverClientError::LengthMismatch {
+            bases: bases.len(),
+            scalars: scalars.len(),
+        });
+    }
+    let mut filtered: FilteredMSM<C> = FilteredMSM {
+        bases: Vec::new(),
+        scalars: Vec::new(),
+        ones_sum: C::Projective::zero(),
+        stats: MSMFilterStats {
+            nof_elements: bases.len(),
+            ..Default::default()
+        },
+    };
+    for (base, scalar) in bases.iter().zip(scalars) {
+        if scalar.is_zero() {
+            filtered.stats.nof_zeros += 1;
+        } else if scalar.is_one() {
+            filtered.ones_sum.add_assign_mixed(base);
+            filtered.stats.nof_ones += 1;
+        } else {
+            filtered.bases.push(*base);
+            filtered.scalars.push(*scalar);
+        }
+    }
+    Ok(filtered)
+}
+
+impl MSMClient {
+    /// Computes an MSM like [`MSMClient::msm`], but filters out trivial scalars first:
+    /// pairs with a zero scalar are dropped and bases with a scalar of one are added up
+    /// on the host. Only the remaining pairs are sent to the device, the host sum is added
+    /// to the result. The device is not used at all if no pairs remain.
+    pub fn msm_filtered<C: MSMCurve>(
+        &self,
+        bases: &[C],
+        scalars: &[C::ScalarField],
+    ) -> Result<(C::Projective, MSMFilterStats)> {
+        let mut filtered = filter_scalars(bases, scalars)?;
+        let element_size =
+            C::base_size() * self.precompute_factor as usize + self.msm_cfg.scalar_size;
+        filtered.stats.saved_bytes =
+            (filtered.stats.nof_zeros + filtered.stats.nof_ones) * element_size;
+        log::debug!(
+            "Filtered {} zero and {} one scalars, {} bytes are not sent",
+            filtered.stats.nof_zeros,
+            filtered.stats.nof_ones,
+            filtered.stats.saved_bytes
+        );
+
+        let result = self.msm(&filtered.bases, &filtered.scalars)? + filtered.ones_sum;
+        Ok((result, filtered.stats))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bn254::{Fr, G1Affine};
+    use ark_ec::AffineCurve;
+
+    #[test]
+    fn filter_zero_and_one_scalars() {
+        let g = G1Affine::prime_subgroup_generator();
+        let bases: Vec<G1Affine> = (1..=5u64).map(|i| g.mul(i).into_affine()).collect();
+        let scalars = [Fr::zero(), Fr::one(), Fr::from(7u64), Fr::one(), Fr::zero()];
+
+        assert!(matches!(
+            filter_scalars(&bases, &scalars[..4]),
+            Err(DriverClientError::LengthMismatch { .. })
+        ));
+        let filtered = filter_scalars(&bases, &scalars).unwrap();
+        assert_eq!(filtered.bases, [bases[2]]);
+        assert_eq!(filtered.scalars, [Fr::from(7u64)]);
+        assert_eq!(filtered.ones_sum, g.mul(6u64));
+        assert_eq!(filtered.stats.nof_zeros, 2);
+        assert_eq!(filtered.stats.nof_ones, 2);
+        assert_eq!(filtered.stats.nof_sent(), 1);
+        assert_eq!(filtered.stats.saved_percentage(), 80.0);
+    }
+}
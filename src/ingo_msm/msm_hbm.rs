@@ -0,0 +1,485 @@
+//! Residency of named base sets in HBM.
+//!
+//! Base sets (e.g. SRS or proving key bases) are loaded into HBM once and kept there
+//! under a name, so MSMs can run against them by name without sending bases again.
+//! The allocator tracks which regions of HBM of the card are taken by which set.
+use super::{msm_api::*, msm_cfg::*, msm_verify::block_hashes};
+use crate::error::*;
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    sync::Mutex,
+};
+
+/// Start addresses of base sets placed by the allocator are aligned to this size.
+pub const HBM_REGION_ALIGNMENT: u64 = 0x1000;
+
+/// A base set resident in HBM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HbmBaseSet {
+    pub name: String,
+    pub curve: Curve,
+    /// Number of bases in the set.
+    pub nof_elements: usize,
+    /// Every base is followed by `precompute_factor - 1` precomputed multiples.
+    pub precompute_factor: u32,
+    /// Start address of the set in HBM.
+    pub addr: u64,
+    /// Size of the set in bytes, including precomputed multiples.
+    pub size: u64,
+    /// Hashes of blocks of the uploaded data, see [`MSMClient::verify_resident_bases`].
+    pub(super) block_hashes: Vec<u64>,
+}
+
+impl HbmBaseSet {
+    /// The region of HBM taken by the set.
+    pub fn region(&self) -> Range<u64> {
+        self.addr..self.addr + self.size
+    }
+}
+
+/// Allocator of HBM regions for base sets of a card.
+#[derive(Debug, Default)]
+pub struct HbmAllocator {
+    capacity: u64,
+    /// Resident sets sorted by address.
+    sets: Vec<HbmBaseSet>,
+    /// Number of running MSMs which read each set, see [`HbmAllocator::pin`].
+    pins: HashMap<String, usize>,
+    /// Sets which are being uploaded, see [`HbmAllocator::reserve`].
+    loading: HashSet<String>,
+}
+
+impl HbmAllocator {
+    pub fn new(capacity: u64) -> Self {
+        HbmAllocator {
+            capacity,
+            sets: Vec::new(),
+            pins: HashMap::new(),
+            loading: HashSet::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Total size of free HBM, it may be fragmented.
+    pub fn free_size(&self) -> u64 {
+        self.capacity - self.sets.iter().map(|s| s.size).sum::<u64>()
+    }
+
+    /// Returns a resident set, sets which are still being uploaded are not returned.
+    pub fn get(&self, name: &str) -> Option<&HbmBaseSet> {
+        self.sets
+            .iter()
+            .find(|s| s.name == name && !self.loading.contains(name))
+    }
+
+    /// Whether a set is reserved by [`HbmAllocator::reserve`] and not committed yet.
+    pub fn is_loading(&self, name: &str) -> bool {
+        self.loading.contains(name)
+    }
+
+    /// Resident sets and sets which are being uploaded, sorted by address.
+    pub fn sets(&self) -> &[HbmBaseSet] {
+        &self.sets
+    }
+
+    /// Finds the first free aligned region of `size` bytes.
+    pub fn find_free(&self, size: u64) -> Result<u64> {
+        let mut addr = 0;
+        for set in self.sets.iter() {
+            if addr + size <= set.addr {
+                return Ok(addr);
+            }
+            addr = addr.max(set.region().end.next_multiple_of(HBM_REGION_ALIGNMENT));
+        }
+        if addr + size <= self.capacity {
+            return Ok(addr);
+        }
+        Err(DriverClientError::HbmOutOfMemory {
+            size,
+            capacity: self.capacity,
+        })
+    }
+
+    /// Checks that a region is inside HBM and doesn't overlap resident sets.
+    pub fn check_free(&self, region: Range<u64>) -> Result<()> {
+        if region.end > self.capacity {
+            return Err(DriverClientError::HbmOutOfMemory {
+                size: region.end - region.start,
+                capacity: self.capacity,
+            });
+        }
+        if let Some(other) = self
+            .sets
+            .iter()
+            .find(|s| s.addr < region.end && region.start < s.region().end)
+        {
+            return Err(DriverClientError::HbmRegionOverlap {
+                name: other.name.clone(),
+                start: region.start,
+                end: region.end,
+            });
+        }
+        Ok(())
+    }
+
+    /// Registers a set, its region must be inside HBM and must not overlap other sets.
+    pub fn insert(&mut self, set: HbmBaseSet) -> Result<()> {
+        if self.sets.iter().any(|s| s.name == set.name) {
+            return Err(DriverClientError::BaseSetExists { name: set.name });
+        }
+        self.check_free(set.region())?;
+        let pos = self.sets.partition_point(|s| s.addr < set.addr);
+        self.sets.insert(pos, set);
+        Ok(())
+    }
+
+    /// Takes the region of a set which is about to be uploaded. Other sets can't be placed over it,
+    /// but the set can't be pinned or removed until it is committed by [`HbmAllocator::commit`]
+    /// or dropped by [`HbmAllocator::release`].
+    pub fn reserve(&mut self, set: HbmBaseSet) -> Result<()> {
+        let name = set.name.clone();
+        self.insert(set)?;
+        self.loading.insert(name);
+        Ok(())
+    }
+
+    /// Makes a reserved set resident once it is uploaded.
+    pub fn commit(&mut self, name: &str) {
+        self.loading.remove(name);
+    }
+
+    /// Drops a reserved set whose upload failed, its region becomes free.
+    pub fn release(&mut self, name: &str) {
+        if self.loading.remove(name) {
+            self.sets.retain(|s| s.name != name);
+        }
+    }
+
+    /// Marks a set as used by a running MSM, it can't be removed until it is unpinned
+    /// as many times as it was pinned.
+    pub fn pin(&mut self, name: &str) -> Result<HbmBaseSet> {
+        let set = self
+            .get(name)
+            .cloned()
+            .ok_or_else(|| DriverClientError::UnknownBaseSet {
+                name: name.to_string(),
+            })?;
+        *self.pins.entry(set.name.clone()).or_default() += 1;
+        Ok(set)
+    }
+
+    pub fn unpin(&mut self, name: &str) {
+        if let Some(pins) = self.pins.get_mut(name) {
+            *pins -= 1;
+            if *pins == 0 {
+                self.pins.remove(name);
+            }
+        }
+    }
+
+    /// Unregisters a set, its region becomes free.
+    /// Returns [`DriverClientError::BaseSetInUse`] if the set is pinned or being uploaded.
+    pub fn remove(&mut self, name: &str) -> Result<HbmBaseSet> {
+        if self.pins.contains_key(name) || self.loading.contains(name) {
+            return Err(DriverClientError::BaseSetInUse {
+                name: name.to_string(),
+            });
+        }
+        match self.sets.iter().position(|s| s.name == name) {
+            Some(pos) => Ok(self.sets.remove(pos)),
+            None => Err(DriverClientError::UnknownBaseSet {
+                name: name.to_string(),
+            }),
+        }
+    }
+}
+
+impl MSMClient {
+    /// Loads a base set into the first free region of HBM and keeps it under `name`.
+    ///
+    /// `points` are in the layout of [`MSMInput::points`], including precomputed multiples
+    /// if the client was created with precompute enabled. The upload is verified according to
+    /// [`MSMClient::with_hbm_verify`], the set is not kept if verification fails.
+    /// Returns [`DriverClientError::HbmNotEnabled`] if the client keeps points in DMA.
+    pub fn load_base_set(&self, name: &str, points: &[u8]) -> Result<HbmBaseSet> {
+        let mut set = self.new_base_set(name, points)?;
+        {
+            let mut hbm = self.hbm.lock().unwrap();
+            set.addr = hbm.find_free(set.size)?;
+            hbm.reserve(set.clone())?;
+        }
+        self.upload_base_set(set, points)
+    }
+
+    /// Loads a base set into HBM at `addr` and keeps it under `name`.
+    /// Returns [`DriverClientError::HbmRegionOverlap`] if the region is taken by another set.
+    pub fn load_base_set_at(&self, name: &str, points: &[u8], addr: u64) -> Result<HbmBaseSet> {
+        let mut set = self.new_base_set(name, points)?;
+        set.addr = addr;
+        self.hbm.lock().unwrap().reserve(set.clone())?;
+        self.upload_base_set(set, points)
+    }
+
+    /// Describes a set of `points` before its region is chosen.
+    fn new_base_set(&self, name: &str, points: &[u8]) -> Result<HbmBaseSet> {
+        if self.mem_type != PointMemoryType::HBM {
+            return Err(DriverClientError::HbmNotEnabled);
+        }
+        let element_size = self.element_size();
+        if !points.len().is_multiple_of(element_size) {
+            return Err(DriverClientError::InputSizeMismatch {
+                expected: points.len().next_multiple_of(element_size),
+                actual: points.len(),
+            });
+        }
+
+        Ok(HbmBaseSet {
+            name: name.to_string(),
+            curve: self.curve,
+            nof_elements: points.len() / element_size,
+            precompute_factor: self.precompute_factor,
+            addr: 0,
+            size: points.len() as u64,
+            block_hashes: block_hashes(points),
+        })
+    }
+
+    /// Uploads a reserved set without holding the allocator, so MSMs against other sets
+    /// and unloads are not blocked meanwhile. The region was reserved before the data is written,
+    /// so other sets are never overwritten.
+    fn upload_base_set(&self, set: HbmBaseSet, points: &[u8]) -> Result<HbmBaseSet> {
+        log::debug!("Load base set {:?} to {:#X?}", set.name, set.region());
+        let uploaded = self
+            .driver_client
+            .dma_write_striped(set.addr, 0u64, points)
+            .and_then(|_| self.check_upload(points, set.addr));
+        let mut hbm = self.hbm.lock().unwrap();
+        match uploaded {
+            Ok(()) => {
+                hbm.commit(&set.name);
+                Ok(set)
+            }
+            Err(e) => {
+                hbm.release(&set.name);
+                Err(e)
+            }
+        }
+    }
+
+    /// Forgets a base set, its region of HBM can be taken by other sets.
+    /// Returns [`DriverClientError::BaseSetInUse`] while an MSM runs against the set.
+    pub fn unload_base_set(&self, name: &str) -> Result<HbmBaseSet> {
+        self.hbm.lock().unwrap().remove(name)
+    }
+
+    pub fn base_set(&self, name: &str) -> Option<HbmBaseSet> {
+        self.hbm.lock().unwrap().get(name).cloned()
+    }
+
+    /// Base sets resident in HBM sorted by address, sets which are being uploaded are not included.
+    pub fn base_sets(&self) -> Vec<HbmBaseSet> {
+        let hbm = self.hbm.lock().unwrap();
+        hbm.sets()
+            .iter()
+            .filter(|s| !hbm.is_loading(&s.name))
+            .cloned()
+            .collect()
+    }
+
+    /// Runs an MSM against a resident base set, see [`MSMClient::run`].
+    ///
+    /// `range` selects bases of the set, the whole set is used if it is `None`.
+    /// `scalars` hold one scalar per selected base.
+    /// The set is pinned while the MSM runs, so it can't be unloaded and overwritten meanwhile.
+    pub fn run_base_set(
+        &self,
+        name: &str,
+        scalars: &[u8],
+        range: Option<Range<usize>>,
+    ) -> Result<MSMResult> {
+        let pinned = PinnedSet::new(&self.hbm, name)?;
+        let set = &pinned.set;
+        let range = range.unwrap_or(0..set.nof_elements);
+        if range.start >= range.end || range.end > set.nof_elements {
+            return Err(DriverClientError::InvalidBaseSetRange {
+                name: set.name.clone(),
+                start: range.start,
+                end: range.end,
+                nof_elements: set.nof_elements,
+            });
+        }
+        let nof_elements = range.end - range.start;
+        check_input_size(scalars.len(), nof_elements * self.msm_cfg.scalar_size)?;
+
+        let addr = set.addr + (range.start * self.element_size()) as u64;
+        self.run(MSMInput {
+            points: None,
+            scalars,
+            params: MSMParams {
+                nof_elements: nof_elements as u32,
+                hbm_point_addr: Some((addr, 0)),
+                tuning: None,
+            },
+        })
+    }
+
+    /// Takes a free region of HBM for `size` bytes of bases for the duration of `f`,
+    /// `f` gets the start address of the region and is responsible for loading the bases.
+    pub(super) fn with_scratch_region<T>(
+        &self,
+        size: usize,
+        f: impl FnOnce(u64) -> Result<T>,
+    ) -> Result<T> {
+        let (name, addr) = {
+            let mut hbm = self.hbm.lock().unwrap();
+            let addr = hbm.find_free(size as u64)?;
+            let name = format!("#scratch-{:#X}", addr);
+            hbm.insert(HbmBaseSet {
+                name: name.clone(),
+                curve: self.curve,
+                nof_elements: size / self.element_size(),
+                precompute_factor: self.precompute_factor,
+                addr,
+                size: size as u64,
+                block_hashes: vec![],
+            })?;
+            (name, addr)
+        };
+        let res = f(addr);
+        self.unload_base_set(&name)?;
+        res
+    }
+
+    /// Checks that `len` bytes written to HBM at `addr` outside of base sets, e.g. scalars
+    /// of a task with [`CoefficientsSource::HBM`] or points of [`MSMClient::load_data_to_hbm`],
+    /// don't overwrite resident base sets, returns [`DriverClientError::HbmRegionOverlap`] otherwise.
+    pub(super) fn check_hbm_write(&self, addr: u64, len: usize) -> Result<()> {
+        self.hbm.lock().unwrap().check_free(addr..addr + len as u64)
+    }
+
+    /// The size in bytes of a base together with its precomputed multiples.
+    fn element_size(&self) -> usize {
+        self.msm_cfg.point_size.unwrap() * self.precompute_factor as usize
+    }
+}
+
+/// A base set pinned in the allocator until the guard is dropped.
+struct PinnedSet<'a> {
+    hbm: &'a Mutex<HbmAllocator>,
+    set: HbmBaseSet,
+}
+
+impl<'a> PinnedSet<'a> {
+    fn new(hbm: &'a Mutex<HbmAllocator>, name: &str) -> Result<Self> {
+        let set = hbm.lock().unwrap().pin(name)?;
+        Ok(PinnedSet { hbm, set })
+    }
+}
+
+impl Drop for PinnedSet<'_> {
+    fn drop(&mut self) {
+        self.hbm.lock().unwrap().unpin(&self.set.name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(name: &str, addr: u64, size: u64) -> HbmBaseSet {
+        HbmBaseSet {
+            name: name.to_string(),
+            curve: Curve::BLS377,
+            nof_elements: size as usize / 96,
+            precompute_factor: 1,
+            addr,
+            size,
+            block_hashes: vec![],
+        }
+    }
+
+    #[test]
+    fn allocate_base_sets() {
+        let mut hbm = HbmAllocator::new(0x10000);
+        hbm.insert(set("a", 0, 0x2000)).unwrap();
+        hbm.insert(set("c", 0x8000, 0x1000)).unwrap();
+        assert_eq!(hbm.find_free(0x3000).unwrap(), 0x2000);
+        assert_eq!(hbm.find_free(0x7000).unwrap(), 0x9000);
+        hbm.check_free(0x2000..0x8000).unwrap();
+        assert!(matches!(
+            hbm.check_free(0x7000..0x9000),
+            Err(DriverClientError::HbmRegionOverlap { name, .. }) if name == "c"
+        ));
+        assert!(matches!(
+            hbm.find_free(0x8000),
+            Err(DriverClientError::HbmOutOfMemory { .. })
+        ));
+
+        assert!(matches!(
+            hbm.insert(set("b", 0x1000, 0x1000)),
+            Err(DriverClientError::HbmRegionOverlap { name, .. }) if name == "a"
+        ));
+        assert!(matches!(
+            hbm.insert(set("a", 0x3000, 0x1000)),
+            Err(DriverClientError::BaseSetExists { .. })
+        ));
+        assert!(matches!(
+            hbm.insert(set("d", 0xF000, 0x2000)),
+            Err(DriverClientError::HbmOutOfMemory { .. })
+        ));
+
+        hbm.insert(set("b", 0x2000, 0x6000)).unwrap();
+        assert_eq!(
+            hbm.sets()
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert_eq!(hbm.free_size(), 0x7000);
+        hbm.pin("a").unwrap();
+        hbm.pin("a").unwrap();
+        hbm.unpin("a");
+        assert!(matches!(
+            hbm.remove("a"),
+            Err(DriverClientError::BaseSetInUse { .. })
+        ));
+        hbm.unpin("a");
+        hbm.remove("a").unwrap();
+        assert_eq!(hbm.find_free(0x2000).unwrap(), 0);
+        assert!(hbm.remove("a").is_err());
+
+        // A set being uploaded takes its region, but can't be used or removed until it is committed.
+        hbm.reserve(set("e", 0, 0x1000)).unwrap();
+        assert!(hbm.get("e").is_none());
+        assert!(matches!(
+            hbm.insert(set("e", 0x1000, 0x1000)),
+            Err(DriverClientError::BaseSetExists { .. })
+        ));
+        assert!(matches!(
+            hbm.check_free(0..0x1000),
+            Err(DriverClientError::HbmRegionOverlap { .. })
+        ));
+        assert!(matches!(
+            hbm.pin("e"),
+            Err(DriverClientError::UnknownBaseSet { .. })
+        ));
+        assert!(matches!(
+            hbm.remove("e"),
+            Err(DriverClientError::BaseSetInUse { .. })
+        ));
+        hbm.commit("e");
+        hbm.pin("e").unwrap();
+        hbm.unpin("e");
+        hbm.reserve(set("f", 0x1000, 0x1000)).unwrap();
+        hbm.release("f");
+        hbm.check_free(0x1000..0x2000).unwrap();
+        hbm.release("e");
+        assert!(hbm.get("e").is_some());
+    }
+}
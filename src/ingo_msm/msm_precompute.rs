@@ -0,0 +1,370 @@
+//! Host-side expansion of bases for precompute images.
+//!
+//! A precompute image expects every base to be followed by its shifted multiples
+//! `2^(w*i) * base` for `i` in `1..precompute_factor`, where `w` is the scalar bit size divided
+//! by the precompute factor. The expanded bases are `precompute_factor` times larger than the
+//! original ones, so they can be cached on disk with [`PrecomputedBases`] and reused between runs.
+use super::{msm_cfg::*, msm_curve::*};
+use crate::{error::*, utils::MappedFile};
+
+use ark_ec::ProjectiveCurve;
+use rayon::prelude::*;
+use std::{
+    ffi::OsString,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+/// Number of bases which are expanded by one rayon task.
+const PRECOMPUTE_BATCH: usize = 2048;
+/// Number of bases which are expanded in memory before they are written to a cache file.
+const CACHE_WINDOW_ELEMENTS: usize = 1 << 20;
+
+const CACHE_MAGIC: [u8; 8] = *b"INGOPREC";
+/// The size of the cache file header, expanded bases start right after it.
+pub const CACHE_HEADER_SIZE: usize = 64;
+
+/// The size in bytes of `nof_elements` bases of the curve `C` expanded with `precompute_factor`.
+pub fn precomputed_size<C: MSMCurve>(nof_elements: usize, precompute_factor: u32) -> usize {
+    nof_elements * C::base_size() * precompute_factor as usize
+}
+
+/// Expands `bases` with `precompute_factor - 1` shifted multiples each and serializes them
+/// in the layout expected by [`DriverPrimitive::set_data`](crate::driver_client::DriverPrimitive::set_data)
+/// and `load_data_to_hbm`. A factor of 1 only serializes the bases.
+///
+/// # Example
+///
+/// ```rust
+/// use ark_bn254::G1Affine;
+/// use ark_ec::AffineCurve;
+/// use ingo_blaze::ingo_msm::*;
+///
+/// let bases = vec![G1Affine::prime_subgroup_generator(); 4];
+/// let points = precompute_bases(&bases, PRECOMPUTE_FACTOR).unwrap();
+/// assert_eq!(points.len(), 4 * 8 * 64);
+/// ```
+pub fn precompute_bases<C: MSMCurve>(bases: &[C], precompute_factor: u32) -> Result<Vec<u8>> {
+    let mut out = vec![0; precomputed_size::<C>(bases.len(), precompute_factor)];
+    precompute_bases_into(bases, precompute_factor, &mut out)?;
+    Ok(out)
+}
+
+/// Checks that scalars of `scalar_bits` bits split into `precompute_factor` windows of equal size.
+pub(super) fn check_precompute_factor(scalar_bits: usize, precompute_factor: u32) -> Result<()> {
+    let factor = precompute_factor as usize;
+    if factor == 0 || !scalar_bits.is_multiple_of(factor) {
+        return Err(DriverClientError::InvalidPrecomputeFactor {
+            factor: precompute_factor,
+        });
+    }
+    Ok(())
+}
+
+/// Same as [`precompute_bases`] but writes the expanded bases into `out`,
+/// e.g. a buffer from [`MSMClient::points_buffer`](super::MSMClient::points_buffer).
+pub fn precompute_bases_into<C: MSMCurve>(
+    bases: &[C],
+    precompute_factor: u32,
+    out: &mut [u8],
+) -> Result<()> {
+    let scalar_bits = coordinate_size::<C::ScalarField>() * 8;
+    check_precompute_factor(scalar_bits, precompute_factor)?;
+    let factor = precompute_factor as usize;
+    let expected = precomputed_size::<C>(bases.len(), precompute_factor);
+    if out.len() != expected {
+        return Err(DriverClientError::InputSizeMismatch {
+            expected,
+            actual: out.len(),
+        });
+    }
+
+    let point_size = C::base_size();
+    let window_bits = scalar_bits / factor;
+    bases
+        .par_chunks(PRECOMPUTE_BATCH)
+        .zip(out.par_chunks_mut(PRECOMPUTE_BATCH * factor * point_size))
+        .for_each(|(batch, out)| {
+            if factor == 1 {
+                for (base, out) in batch.iter().zip(out.chunks_exact_mut(point_size)) {
+                    base.write_base(out);
+                }
+                return;
+            }
+
+            let mut multiples = Vec::with_capacity(batch.len() * factor);
+            for base in batch {
+                let mut current = base.into_projective();
+                for _ in 0..factor {
+                    multiples.push(current);
+                    for _ in 0..window_bits {
+                        current.double_in_place();
+                    }
+                }
+            }
+            let multiples = C::Projective::batch_normalization_into_affine(&multiples);
+            for (point, out) in multiples.iter().zip(out.chunks_exact_mut(point_size)) {
+                point.write_base(out);
+            }
+        });
+    Ok(())
+}
+
+/// Expanded bases stored in a cache file.
+///
+/// The file starts with a [`CACHE_HEADER_SIZE`] bytes header: magic `INGOPREC`, curve id,
+/// precompute factor, number of bases, base size and a fingerprint of the original bases,
+/// all little-endian. The expanded bases follow in the layout of [`precompute_bases`].
+/// The file is memory-mapped, so [`PrecomputedBases::points`] can be fed to the device
+/// without reading the whole file into RAM.
+pub struct PrecomputedBases {
+    file: MappedFile,
+    nof_elements: usize,
+    fingerprint: u64,
+}
+
+impl PrecomputedBases {
+    /// Expands `bases` and writes them into a cache file at `path`.
+    /// The file is written next to `path` first and renamed when complete,
+    /// so an interrupted run never leaves a truncated cache behind.
+    pub fn create<C: MSMCurve, P: AsRef<Path>>(
+        bases: &[C],
+        precompute_factor: u32,
+        path: P,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        let mut tmp_path = OsString::from(path);
+        tmp_path.push(".tmp");
+
+        log::debug!("Write precomputed bases to {:?}", path);
+        let mut writer = BufWriter::new(File::create(&tmp_path)?);
+        writer.write_all(&header::<C>(
+            precompute_factor,
+            bases.len(),
+            fingerprint(bases),
+        ))?;
+        for window in bases.chunks(CACHE_WINDOW_ELEMENTS) {
+            writer.write_all(&precompute_bases(window, precompute_factor)?)?;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp_path, path)?;
+
+        Self::open::<C, _>(path, precompute_factor)
+    }
+
+    /// Opens a cache file and checks that it holds bases of the curve `C`
+    /// expanded with `precompute_factor`.
+    pub fn open<C: MSMCurve, P: AsRef<Path>>(path: P, precompute_factor: u32) -> Result<Self> {
+        let file = MappedFile::open(path)?;
+        if file.len() < CACHE_HEADER_SIZE || file[0..8] != CACHE_MAGIC {
+            return Err(invalid_cache("missing header".to_string()));
+        }
+        let read_u32 = |pos: usize| u32::from_le_bytes(file[pos..pos + 4].try_into().unwrap());
+        let read_u64 = |pos: usize| u64::from_le_bytes(file[pos..pos + 8].try_into().unwrap());
+
+        let curve = read_u32(8);
+        if curve != curve_id(C::CURVE) {
+            return Err(invalid_cache(format!(
+                "curve id {} instead of {:?}",
+                curve,
+                C::CURVE
+            )));
+        }
+        let factor = read_u32(12);
+        if factor != precompute_factor {
+            return Err(invalid_cache(format!(
+                "precompute factor {} instead of {}",
+                factor, precompute_factor
+            )));
+        }
+        let nof_elements = read_u64(16) as usize;
+        let base_size = read_u64(24) as usize;
+        if base_size != C::base_size() {
+            return Err(invalid_cache(format!(
+                "base size {} instead of {}",
+                base_size,
+                C::base_size()
+            )));
+        }
+        let expected = CACHE_HEADER_SIZE + precomputed_size::<C>(nof_elements, precompute_factor);
+        if file.len() != expected {
+            return Err(invalid_cache(format!(
+                "file size {} instead of {}",
+                file.len(),
+                expected
+            )));
+        }
+        let fingerprint = read_u64(32);
+
+        Ok(PrecomputedBases {
+            file,
+            nof_elements,
+            fingerprint,
+        })
+    }
+
+    /// Opens a cache file if it holds exactly `bases` expanded with `precompute_factor`,
+    /// otherwise expands the bases and (re)creates the file.
+    pub fn open_or_create<C: MSMCurve, P: AsRef<Path>>(
+        bases: &[C],
+        precompute_factor: u32,
+        path: P,
+    ) -> Result<Self> {
+        let path = path.as_ref();
+        match Self::open::<C, _>(path, precompute_factor) {
+            Ok(cache)
+                if cache.nof_elements == bases.len() && cache.fingerprint == fingerprint(bases) =>
+            {
+                log::debug!("Use precomputed bases from {:?}", path);
+                return Ok(cache);
+            }
+            Ok(_) => log::debug!("Precomputed bases in {:?} are outdated", path),
+            Err(e) => log::debug!("Can't use precomputed bases from {:?}: {}", path, e),
+        }
+        Self::create(bases, precompute_factor, path)
+    }
+
+    /// Number of original bases.
+    pub fn nof_elements(&self) -> usize {
+        self.nof_elements
+    }
+
+    /// Expanded bases in the layout of [`precompute_bases`].
+    pub fn points(&self) -> &[u8] {
+        &self.file[CACHE_HEADER_SIZE..]
+    }
+
+    /// Drops loaded pages of the expanded bases `start..end` from memory, see [`MappedFile::release`].
+    pub fn release(&self, start: usize, end: usize) {
+        self.file
+            .release(CACHE_HEADER_SIZE + start, CACHE_HEADER_SIZE + end);
+    }
+}
+
+impl AsRef<[u8]> for PrecomputedBases {
+    fn as_ref(&self) -> &[u8] {
+        self.points()
+    }
+}
+
+fn header<C: MSMCurve>(precompute_factor: u32, nof_elements: usize, fingerprint: u64) -> Vec<u8> {
+    let mut header = Vec::with_capacity(CACHE_HEADER_SIZE);
+    header.extend_from_slice(&CACHE_MAGIC);
+    header.extend_from_slice(&curve_id(C::CURVE).to_le_bytes());
+    header.extend_from_slice(&precompute_factor.to_le_bytes());
+    header.extend_from_slice(&(nof_elements as u64).to_le_bytes());
+    header.extend_from_slice(&(C::base_size() as u64).to_le_bytes());
+    header.extend_from_slice(&fingerprint.to_le_bytes());
+    header.resize(CACHE_HEADER_SIZE, 0);
+    header
+}
+
+/// Curve ids are the same as in the image parameters.
+fn curve_id(curve: Curve) -> u32 {
+    match curve {
+        Curve::BLS377 => 0,
+        Curve::BN254 => 1,
+        Curve::BLS381 => 2,
+    }
+}
+
+/// A hash of the original bases, it detects a cache file made for other bases.
+///
+/// The hash is stored on disk, so it must not change between builds: every batch of
+/// [`PRECOMPUTE_BATCH`] bases serialized by [`MSMCurve::write_base`] is hashed with 64-bit FNV-1a,
+/// and the little-endian batch hashes are hashed again with FNV-1a.
+fn fingerprint<C: MSMCurve>(bases: &[C]) -> u64 {
+    let hashes: Vec<u8> = bases
+        .par_chunks(PRECOMPUTE_BATCH)
+        .flat_map_iter(|batch| {
+            let mut base = vec![0; C::base_size()];
+            let hash = batch.iter().fold(FNV_OFFSET_BASIS, |hash, point| {
+                point.write_base(&mut base);
+                fnv1a(hash, &base)
+            });
+            hash.to_le_bytes()
+        })
+        .collect();
+    fnv1a(FNV_OFFSET_BASIS, &hashes)
+}
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Continues the 64-bit FNV-1a hash `hash` with `bytes`.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+fn invalid_cache(reason: String) -> DriverClientError {
+    DriverClientError::InvalidPrecomputeCache { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ec::AffineCurve;
+    use ark_ff::{BigInteger, PrimeField};
+
+    #[test]
+    fn fingerprint_is_stable() {
+        // Test vectors of the FNV-1a specification.
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv1a(FNV_OFFSET_BASIS, b"foobar"), 0x8594_4171_f739_67e8);
+
+        let bases = [ark_bn254::G1Affine::prime_subgroup_generator(); 3];
+        let mut serialized = vec![0; 3 * ark_bn254::G1Affine::base_size()];
+        for (base, out) in bases
+            .iter()
+            .zip(serialized.chunks_mut(ark_bn254::G1Affine::base_size()))
+        {
+            base.write_base(out);
+        }
+        let batch = fnv1a(FNV_OFFSET_BASIS, &serialized);
+        assert_eq!(
+            fingerprint(&bases),
+            fnv1a(FNV_OFFSET_BASIS, &batch.to_le_bytes())
+        );
+    }
+
+    #[test]
+    fn precompute_matches_scalar_multiplication() {
+        let base = ark_bls12_377::G1Affine::prime_subgroup_generator()
+            .mul(3u64)
+            .into_affine();
+        let points = precompute_bases(&[base], 8).unwrap();
+        assert_eq!(points.len(), 8 * 96);
+
+        let two = num_bigint::BigUint::from(2u32);
+        for (i, point) in points.chunks(96).enumerate() {
+            let coeff = ark_bls12_377::Fr::from(two.pow(32 * i as u32));
+            let expected = base.mul(coeff).into_affine();
+            assert_eq!(&point[..48], expected.x.into_repr().to_bytes_le());
+            assert_eq!(&point[48..], expected.y.into_repr().to_bytes_le());
+        }
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let path = std::env::temp_dir().join(format!("ingo_precompute_{}", std::process::id()));
+        let bases = vec![ark_bn254::G1Affine::prime_subgroup_generator(); 3];
+
+        let cache = PrecomputedBases::open_or_create(&bases, 8, &path).unwrap();
+        assert_eq!(cache.nof_elements(), 3);
+        assert_eq!(cache.points(), precompute_bases(&bases, 8).unwrap());
+        assert!(PrecomputedBases::open::<ark_bn254::G1Affine, _>(&path, 1).is_err());
+        assert!(PrecomputedBases::open::<ark_bls12_381::G1Affine, _>(&path, 8).is_err());
+
+        let other = vec![ark_bn254::G1Affine::prime_subgroup_generator(); 2];
+        let cache = PrecomputedBases::open_or_create(&other, 8, &path).unwrap();
+        assert_eq!(cache.nof_elements(), 2);
+        fs::remove_file(&path).unwrap();
+    }
+}
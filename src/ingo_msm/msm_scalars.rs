@@ -0,0 +1,242 @@
+//! Validated scalars of an MSM.
+//!
+//! The core takes scalars as canonical little-endian integers of the scalar size of the curve.
+//! Montgomery-form limbs or values not below the scalar field modulus are silently computed
+//! as other scalars, so [`Scalars`] checks and converts them before they are sent to the device.
+use super::{msm_api::*, msm_cfg::*, msm_curve::*};
+use crate::error::*;
+
+use ark_ff::{BigInteger, BigInteger256, Fp256, Fp256Parameters, FromBytes, PrimeField};
+use std::ops::Deref;
+
+/// Encoding of scalars given as bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScalarEncoding {
+    /// Little-endian integers below the scalar field modulus.
+    Canonical,
+    /// Little-endian limbs of field elements in Montgomery form, as arkworks stores them in memory.
+    Montgomery,
+}
+
+/// Scalars of a curve in the layout of [`MSMInput::scalars`], every scalar is canonical
+/// and below the scalar field modulus. Build them with [`ScalarsBuilder`].
+///
+/// # Example
+///
+/// ```rust
+/// use ark_bn254::{Fr, G1Affine};
+/// use ingo_blaze::ingo_msm::*;
+///
+/// let scalars = Scalars::builder(Curve::BN254)
+///     .fields::<G1Affine>(&[Fr::from(1u64), Fr::from(2u64)])
+///     .unwrap()
+///     .build();
+/// assert_eq!(scalars.len(), 2);
+/// assert_eq!(scalars.as_bytes().len(), 64);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scalars {
+    curve: Curve,
+    scalar_size: usize,
+    data: Vec<u8>,
+}
+
+impl Scalars {
+    pub fn builder(curve: Curve) -> ScalarsBuilder {
+        ScalarsBuilder::new(curve)
+    }
+
+    pub fn curve(&self) -> Curve {
+        self.curve
+    }
+
+    /// Number of scalars.
+    pub fn len(&self) -> usize {
+        self.data.len() / self.scalar_size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl Deref for Scalars {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+/// Builder of [`Scalars`] of a curve. Every added scalar is validated,
+/// the first invalid one fails the whole call.
+pub struct ScalarsBuilder {
+    curve: Curve,
+    scalar_size: usize,
+    data: Vec<u8>,
+}
+
+impl ScalarsBuilder {
+    pub fn new(curve: Curve) -> Self {
+        ScalarsBuilder {
+            curve,
+            scalar_size: scalar_size(curve),
+            data: Vec::new(),
+        }
+    }
+
+    /// Adds scalar field elements of the curve of points `C`.
+    /// Returns [`DriverClientError::CurveMismatch`] if `C` belongs to another curve.
+    pub fn fields<C: MSMCurve>(mut self, scalars: &[C::ScalarField]) -> Result<Self> {
+        if C::CURVE != self.curve {
+            return Err(DriverClientError::CurveMismatch {
+                client: format!("{:?}", self.curve),
+                requested: format!("{:?}", C::CURVE),
+            });
+        }
+        let start = self.data.len();
+        self.data
+            .resize(start + scalars.len() * self.scalar_size, 0);
+        for (s, out) in scalars
+            .iter()
+            .zip(self.data[start..].chunks_exact_mut(self.scalar_size))
+        {
+            let bytes = s.into_repr().to_bytes_le();
+            out[..bytes.len()].copy_from_slice(&bytes);
+        }
+        Ok(self)
+    }
+
+    /// Adds scalars given as little-endian integers of `scalar_size` bytes each.
+    ///
+    /// Returns [`DriverClientError::InputSizeMismatch`] if the bytes are not a whole number of
+    /// scalars, and [`DriverClientError::ScalarOutOfRange`] with the index of the first scalar
+    /// (counting scalars added before) which is not below the scalar field modulus.
+    /// Montgomery-form scalars are converted into canonical ones.
+    pub fn bytes(mut self, bytes: &[u8], encoding: ScalarEncoding) -> Result<Self> {
+        if !bytes.len().is_multiple_of(self.scalar_size) {
+            return Err(DriverClientError::InputSizeMismatch {
+                expected: bytes.len().next_multiple_of(self.scalar_size),
+                actual: bytes.len(),
+            });
+        }
+        let convert = match self.curve {
+            Curve::BLS377 => canonicalize::<ark_bls12_377::FrParameters>,
+            Curve::BLS381 => canonicalize::<ark_bls12_381::FrParameters>,
+            Curve::BN254 => canonicalize::<ark_bn254::FrParameters>,
+        };
+        let first = self.data.len() / self.scalar_size;
+        let start = self.data.len();
+        self.data.extend_from_slice(bytes);
+        for (i, scalar) in self.data[start..]
+            .chunks_exact_mut(self.scalar_size)
+            .enumerate()
+        {
+            if !convert(scalar, encoding) {
+                return Err(DriverClientError::ScalarOutOfRange { index: first + i });
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn build(self) -> Scalars {
+        Scalars {
+            curve: self.curve,
+            scalar_size: self.scalar_size,
+            data: self.data,
+        }
+    }
+}
+
+/// The size in bytes of a scalar of the curve in the layout of the core.
+fn scalar_size(curve: Curve) -> usize {
+    match curve {
+        Curve::BLS377 => coordinate_size::<ark_bls12_377::Fr>(),
+        Curve::BLS381 => coordinate_size::<ark_bls12_381::Fr>(),
+        Curve::BN254 => coordinate_size::<ark_bn254::Fr>(),
+    }
+}
+
+/// Checks that a scalar is below the modulus and rewrites it in canonical form.
+fn canonicalize<P: Fp256Parameters>(scalar: &mut [u8], encoding: ScalarEncoding) -> bool {
+    let repr = match BigInteger256::read(&scalar[..]) {
+        Ok(repr) if repr < P::MODULUS => repr,
+        _ => return false,
+    };
+    if encoding == ScalarEncoding::Montgomery {
+        let canonical = Fp256::<P>::new(repr).into_repr();
+        scalar.copy_from_slice(&canonical.to_bytes_le());
+    }
+    true
+}
+
+impl MSMClient {
+    /// Checks that scalars belong to the curve of the client and hold `nof_elements` scalars
+    /// of the size expected by the core, so they can be passed as [`MSMInput::scalars`].
+    pub fn check_scalars(&self, scalars: &Scalars, nof_elements: usize) -> Result<()> {
+        if scalars.curve != self.curve {
+            return Err(DriverClientError::CurveMismatch {
+                client: format!("{:?}", self.curve),
+                requested: format!("{:?}", scalars.curve),
+            });
+        }
+        let expected = nof_elements * self.msm_cfg.scalar_size;
+        if scalars.scalar_size != self.msm_cfg.scalar_size || scalars.data.len() != expected {
+            return Err(DriverClientError::InputSizeMismatch {
+                expected,
+                actual: scalars.data.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_bls12_377::{Fr, G1Affine};
+    use ark_ff::{Field, FpParameters};
+
+    #[test]
+    fn validate_and_convert_scalars() {
+        let s = Fr::from(5u64);
+        let canonical = s.into_repr().to_bytes_le();
+        // Fr is stored as Montgomery limbs.
+        let montgomery = s.0.to_bytes_le();
+        assert_ne!(canonical, montgomery);
+
+        let scalars = Scalars::builder(Curve::BLS377)
+            .bytes(&montgomery, ScalarEncoding::Montgomery)
+            .unwrap()
+            .bytes(&canonical, ScalarEncoding::Canonical)
+            .unwrap()
+            .fields::<G1Affine>(&[s.square()])
+            .unwrap()
+            .build();
+        assert_eq!(scalars.len(), 3);
+        assert_eq!(scalars[..32], canonical);
+        assert_eq!(scalars[32..64], canonical);
+        assert_eq!(scalars[64..], Fr::from(25u64).into_repr().to_bytes_le());
+
+        let modulus = ark_bls12_377::FrParameters::MODULUS.to_bytes_le();
+        assert!(matches!(
+            Scalars::builder(Curve::BLS377).bytes(
+                &[canonical.clone(), modulus].concat(),
+                ScalarEncoding::Canonical
+            ),
+            Err(DriverClientError::ScalarOutOfRange { index: 1 })
+        ));
+        assert!(matches!(
+            Scalars::builder(Curve::BLS377).bytes(&canonical[..31], ScalarEncoding::Canonical),
+            Err(DriverClientError::InputSizeMismatch { .. })
+        ));
+        assert!(matches!(
+            Scalars::builder(Curve::BN254).fields::<G1Affine>(&[s]),
+            Err(DriverClientError::CurveMismatch { .. })
+        ));
+    }
+}
@@ -0,0 +1,203 @@
+//! Performance counters of the last MSM task.
+//!
+//! The core counts clocks of each phase of a task, clocks when EC adders are busy and
+//! occupancy of input FIFOs. Counters are 64-bit values exposed as LO/HI register pairs.
+use super::{msm_api::*, msm_hw_code::*};
+use crate::error::*;
+
+use std::time::Duration;
+
+/// Clock frequency of the MSM core used for wall time estimates by default.
+pub const DEFAULT_CORE_CLOCK_HZ: u64 = 250_000_000;
+
+/// Clock counters of one phase of a task.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MSMPhaseCounters {
+    pub total_clocks: u64,
+    /// Clocks when EC adders were busy.
+    pub busy_ec_adder_clocks: u64,
+}
+
+impl MSMPhaseCounters {
+    /// Percentage of clocks of the phase when EC adders were busy.
+    pub fn ec_adder_utilization(&self) -> f64 {
+        percentage(self.busy_ec_adder_clocks, self.total_clocks)
+    }
+}
+
+/// Counters of an input FIFO of the core in the first phase of a task.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct MSMFifoCounters {
+    /// Clocks when the FIFO was transferring data.
+    pub busy_clocks: u64,
+    /// Number of times the FIFO ran empty.
+    pub nof_empty: u64,
+}
+
+/// Performance counters of the last completed MSM task.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MSMTaskStats {
+    /// Phase 1 accumulates buckets, phases 2 and 3 do the final accumulation.
+    pub phases: [MSMPhaseCounters; 3],
+    pub coefficients_fifo: MSMFifoCounters,
+    pub bases_fifo: MSMFifoCounters,
+    pub bucket_accumulation_completed: bool,
+    pub final_accumulation_completed: bool,
+    /// Clock frequency of the core used for wall time estimates.
+    pub core_clock_hz: u64,
+}
+
+impl MSMTaskStats {
+    /// Clocks of all phases of the task.
+    pub fn total_clocks(&self) -> u64 {
+        self.phases.iter().map(|p| p.total_clocks).sum()
+    }
+
+    /// Percentage of clocks of all phases when EC adders were busy.
+    pub fn ec_adder_utilization(&self) -> f64 {
+        let busy = self.phases.iter().map(|p| p.busy_ec_adder_clocks).sum();
+        percentage(busy, self.total_clocks())
+    }
+
+    /// Estimated wall time of a phase, `phase` is from 1 to 3.
+    pub fn phase_time(&self, phase: usize) -> Duration {
+        self.clocks_to_time(self.phases[phase - 1].total_clocks)
+    }
+
+    /// Estimated wall time of the task on the core.
+    pub fn wall_time(&self) -> Duration {
+        self.clocks_to_time(self.total_clocks())
+    }
+
+    fn clocks_to_time(&self, clocks: u64) -> Duration {
+        if self.core_clock_hz == 0 {
+            return Duration::ZERO;
+        }
+        let nanos = clocks as u128 * 1_000_000_000 / self.core_clock_hz as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+fn percentage(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        return 0.0;
+    }
+    part as f64 * 100.0 / total as f64
+}
+
+impl MSMClient {
+    /// Sets the clock frequency of the core used for wall time estimates of [`MSMTaskStats`].
+    pub fn with_core_clock(mut self, core_clock_hz: u64) -> Self {
+        self.core_clock_hz = core_clock_hz;
+        self
+    }
+
+    /// Reads performance counters of the last completed task.
+    pub fn last_task_stats(&self) -> Result<MSMTaskStats> {
+        use INGO_MSM_ADDR::*;
+
+        let phase = |total_lo, total_hi, busy_lo, busy_hi| -> Result<MSMPhaseCounters> {
+            Ok(MSMPhaseCounters {
+                total_clocks: self.read_counter(total_lo, total_hi)?,
+                busy_ec_adder_clocks: self.read_counter(busy_lo, busy_hi)?,
+            })
+        };
+        let fifo = |busy_lo, busy_hi, empty_lo, empty_hi| -> Result<MSMFifoCounters> {
+            Ok(MSMFifoCounters {
+                busy_clocks: self.read_counter(busy_lo, busy_hi)?,
+                nof_empty: self.read_counter(empty_lo, empty_hi)?,
+            })
+        };
+        let flag = |offset| -> Result<bool> {
+            self.driver_client
+                .ctrl_read_u32(self.driver_client.cfg.ctrl_baseaddr, offset)
+                .map(|v| v != 0)
+        };
+
+        Ok(MSMTaskStats {
+            phases: [
+                phase(
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE1_TOTAL_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE1_TOTAL_CLOCKS_HI,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BUSY_ECADDER_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BUSY_ECADDER_CLOCKS_HI,
+                )?,
+                phase(
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE2_TOTAL_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE2_TOTAL_CLOCKS_HI,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE2_BUSY_ECADDER_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE2_BUSY_ECADDER_CLOCKS_HI,
+                )?,
+                phase(
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE3_TOTAL_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE3_TOTAL_CLOCKS_HI,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE3_BUSY_ECADDER_CLOCKS_LO,
+                    ADDR_HIF2CPU_C_LAST_TASK_PHASE3_BUSY_ECADDER_CLOCKS_HI,
+                )?,
+            ],
+            coefficients_fifo: fifo(
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_COEFFICIENTS_FIFO_BUSY_CLOCKS_LO,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_COEFFICIENTS_FIFO_BUSY_CLOCKS_HI,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_COEFFICIENTS_FIFO_NOF_EMPTY_LO,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_COEFFICIENTS_FIFO_NOF_EMPTY_HI,
+            )?,
+            bases_fifo: fifo(
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BASES_FIFO_BUSY_CLOCKS_LO,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BASES_FIFO_BUSY_CLOCKS_HI,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BASES_FIFO_NOF_EMPTY_LO,
+                ADDR_HIF2CPU_C_LAST_TASK_PHASE1_BASES_FIFO_NOF_EMPTY_HI,
+            )?,
+            bucket_accumulation_completed: flag(
+                ADDR_HIF2CPU_E_BUCKET_ACCUMULATION_PHASE_COMPLETED,
+            )?,
+            final_accumulation_completed: flag(ADDR_HIF2CPU_E_FINAL_ACCUMULATION_PHASE_COMPLETED)?,
+            core_clock_hz: self.core_clock_hz,
+        })
+    }
+
+    /// Reads a 64-bit counter from a LO/HI register pair.
+    /// HI is read again after LO, so a carry between the reads is not missed.
+    fn read_counter(&self, lo: INGO_MSM_ADDR, hi: INGO_MSM_ADDR) -> Result<u64> {
+        let base = self.driver_client.cfg.ctrl_baseaddr;
+        loop {
+            let high = self.driver_client.ctrl_read_u32(base, hi)?;
+            let low = self.driver_client.ctrl_read_u32(base, lo)?;
+            if self.driver_client.ctrl_read_u32(base, hi)? == high {
+                return Ok((high as u64) << 32 | low as u64);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utilization_and_wall_time() {
+        let stats = MSMTaskStats {
+            phases: [
+                MSMPhaseCounters {
+                    total_clocks: 1_000_000,
+                    busy_ec_adder_clocks: 900_000,
+                },
+                MSMPhaseCounters {
+                    total_clocks: 200_000,
+                    busy_ec_adder_clocks: 50_000,
+                },
+                MSMPhaseCounters::default(),
+            ],
+            coefficients_fifo: MSMFifoCounters::default(),
+            bases_fifo: MSMFifoCounters::default(),
+            bucket_accumulation_completed: true,
+            final_accumulation_completed: true,
+            core_clock_hz: DEFAULT_CORE_CLOCK_HZ,
+        };
+        assert_eq!(stats.total_clocks(), 1_200_000);
+        assert_eq!(stats.phases[0].ec_adder_utilization(), 90.0);
+        assert_eq!(stats.phases[2].ec_adder_utilization(), 0.0);
+        assert!((stats.ec_adder_utilization() - 950_000.0 * 100.0 / 1_200_000.0).abs() < 1e-9);
+        assert_eq!(stats.phase_time(1), Duration::from_millis(4));
+        assert_eq!(stats.wall_time(), Duration::from_micros(4800));
+    }
+}
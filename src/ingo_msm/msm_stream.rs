@@ -0,0 +1,217 @@
+//! Streaming of MSM inputs paced by the input FIFOs of the core.
+//!
+//! A write into a full FIFO blocks inside the driver until the core takes the data, so a
+//! stalled core hangs the caller. [`MSMClient::set_data_streaming`] sends a chunk only when
+//! the FIFOs have room for it, reports progress of the task and returns
+//! [`DriverClientError::CoreStalled`] if the core stops consuming data.
+use super::{msm_api::*, msm_hw_code::*};
+use crate::error::*;
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Pacing of [`MSMClient::set_data_streaming`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MSMStreamConfig {
+    /// A chunk is sent only if the FIFOs hold no more than this number of elements with it.
+    pub max_fifo_elements: u32,
+    /// The core is considered stalled if neither FIFO levels nor the number of elements left
+    /// in the current task change for this time while the stream waits for room.
+    pub stall_timeout: Duration,
+    /// Pause between reads of FIFO levels while the stream waits for room.
+    pub poll_interval: Duration,
+}
+
+impl Default for MSMStreamConfig {
+    fn default() -> Self {
+        MSMStreamConfig {
+            max_fifo_elements: 4 * DEFAULT_DMA_CHUNK_SIZE as u32,
+            stall_timeout: Duration::from_secs(5),
+            poll_interval: Duration::from_micros(50),
+        }
+    }
+}
+
+/// Progress of a streamed task reported after every chunk.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MSMStreamProgress {
+    /// Elements sent to the core.
+    pub sent: usize,
+    /// Elements consumed by the core.
+    pub consumed: usize,
+    pub nof_elements: usize,
+}
+
+/// State of the core which is watched for changes while the stream waits.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct CoreState {
+    coefficients_in_fifo: u32,
+    bases_in_fifo: u32,
+    elements_left: u32,
+}
+
+/// Detects that the core state doesn't change for longer than a timeout.
+struct StallDetector {
+    last: Option<CoreState>,
+    since: Instant,
+    timeout: Duration,
+}
+
+impl StallDetector {
+    fn new(timeout: Duration, now: Instant) -> Self {
+        StallDetector {
+            last: None,
+            since: now,
+            timeout,
+        }
+    }
+
+    /// Returns for how long the core is stalled if it is longer than the timeout.
+    fn update(&mut self, state: CoreState, now: Instant) -> Option<Duration> {
+        if self.last != Some(state) {
+            self.last = Some(state);
+            self.since = now;
+            return None;
+        }
+        let stalled_for = now.duration_since(self.since);
+        (stalled_for > self.timeout).then_some(stalled_for)
+    }
+}
+
+impl MSMClient {
+    /// Sets data for a pushed task like [`DriverPrimitive::set_data`](crate::driver_client::DriverPrimitive::set_data),
+    /// but sends every DMA chunk only when the input FIFOs of the core have room for it.
+    ///
+    /// `progress` is called after every chunk and once the whole input is sent.
+    /// Data which goes to HBM is written before streaming. Returns [`DriverClientError::CoreStalled`]
+    /// if the core state doesn't change for [`MSMStreamConfig::stall_timeout`], the rest of the
+    /// input is not sent in that case and the core has to be reset.
+    pub fn set_data_streaming(
+        &self,
+        data: MSMInput,
+        cfg: &MSMStreamConfig,
+        mut progress: impl FnMut(MSMStreamProgress),
+    ) -> Result<()> {
+        let tuning = self.task_tuning(&data.params);
+        if let (Some(p), Some((addr, offset))) = (data.points, data.params.hbm_point_addr) {
+            self.load_data_to_hbm(p, addr, offset)?;
+        }
+        if let CoefficientsSource::HBM { addr } = tuning.coefficients_source {
+            self.check_hbm_write(addr, data.scalars.len())?;
+            self.driver_client
+                .dma_write_striped(addr, 0u64, data.scalars)?;
+        }
+
+        let nof_elements = data.params.nof_elements as usize;
+        let chunk_size = tuning.dma_chunk_size;
+        let chunks = nof_elements.div_ceil(chunk_size);
+        let segments = self.stream_segments(&data);
+        // Segments of a chunk: scalars and points, or only one of them.
+        let per_chunk = segments.len().checked_div(chunks).unwrap_or(1).max(1);
+
+        let mut sent = 0;
+        for (i, chunk) in segments.chunks(per_chunk).enumerate() {
+            let nof_chunk_elements = chunk_size.min(nof_elements - i * chunk_size);
+            self.wait_fifo_room(nof_chunk_elements as u32, cfg)?;
+            self.driver_client.dma_write_vectored(chunk)?;
+            sent += nof_chunk_elements;
+            progress(MSMStreamProgress {
+                sent,
+                consumed: self.consumed_elements(nof_elements, sent)?,
+                nof_elements,
+            });
+        }
+        if segments.is_empty() {
+            progress(MSMStreamProgress {
+                sent: nof_elements,
+                consumed: self.consumed_elements(nof_elements, nof_elements)?,
+                nof_elements,
+            });
+        }
+        Ok(())
+    }
+
+    /// Waits until both FIFOs can take `nof_elements` more elements.
+    /// A chunk larger than the limit is sent once the FIFOs are empty.
+    fn wait_fifo_room(&self, nof_elements: u32, cfg: &MSMStreamConfig) -> Result<()> {
+        let mut detector = StallDetector::new(cfg.stall_timeout, Instant::now());
+        loop {
+            let state = self.core_state()?;
+            let level = state.coefficients_in_fifo.max(state.bases_in_fifo);
+            if level == 0 || level + nof_elements <= cfg.max_fifo_elements {
+                return Ok(());
+            }
+            if let Some(stalled_for) = detector.update(state, Instant::now()) {
+                log::error!("MSM core is stalled: {:?}", state);
+                return Err(DriverClientError::CoreStalled {
+                    elements_left: state.elements_left,
+                    stalled_for,
+                });
+            }
+            thread::sleep(cfg.poll_interval);
+        }
+    }
+
+    fn core_state(&self) -> Result<CoreState> {
+        let read = |offset| {
+            self.driver_client
+                .ctrl_read_u32(self.driver_client.cfg.ctrl_baseaddr, offset)
+        };
+        Ok(CoreState {
+            coefficients_in_fifo: read(
+                INGO_MSM_ADDR::ADDR_AXI2CPU_C_NUMBER_OF_COEFFICIENTS_IN_AXI_DMA_FIFO,
+            )?,
+            bases_in_fifo: read(INGO_MSM_ADDR::ADDR_AXI2CPU_C_NUMBER_OF_BASES_IN_AXI_DMA_FIFO)?,
+            elements_left: read(INGO_MSM_ADDR::ADDR_HIF2CPU_C_NOF_ELEMENTS_LEFT_IN_CURRENT_TASK)?,
+        })
+    }
+
+    /// Elements of a task of `nof_elements` consumed by the core, estimated from the elements
+    /// left in the current task of the core. It never exceeds the number of `sent` elements.
+    fn consumed_elements(&self, nof_elements: usize, sent: usize) -> Result<usize> {
+        let left = self.driver_client.ctrl_read_u32(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_MSM_ADDR::ADDR_HIF2CPU_C_NOF_ELEMENTS_LEFT_IN_CURRENT_TASK,
+        )?;
+        Ok(nof_elements.saturating_sub(left as usize).min(sent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_stalled_core() {
+        let start = Instant::now();
+        let state = CoreState {
+            coefficients_in_fifo: 8192,
+            bases_in_fifo: 8192,
+            elements_left: 100,
+        };
+        let mut detector = StallDetector::new(Duration::from_secs(1), start);
+        assert_eq!(detector.update(state, start), None);
+        assert_eq!(
+            detector.update(state, start + Duration::from_millis(900)),
+            None
+        );
+
+        // The core made progress, the timer restarts.
+        let progressed = CoreState {
+            elements_left: 99,
+            ..state
+        };
+        let now = start + Duration::from_millis(1500);
+        assert_eq!(detector.update(progressed, now), None);
+        assert_eq!(
+            detector.update(progressed, now + Duration::from_secs(1)),
+            None
+        );
+        assert_eq!(
+            detector.update(progressed, now + Duration::from_secs(2)),
+            Some(Duration::from_secs(2))
+        );
+    }
+}
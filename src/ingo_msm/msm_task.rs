@@ -0,0 +1,210 @@
+//! Submission of MSM tasks through the task queue of the core.
+//!
+//! Every pushed task gets a label which the core returns together with its result.
+//! Results are popped from the core by whichever caller polls first and kept by label
+//! until the owner of the task takes them, so several tasks can be outstanding at once.
+use super::msm_api::*;
+use crate::{driver_client::*, error::*};
+
+use std::{
+    collections::{HashMap, HashSet},
+    hint,
+    sync::Mutex,
+};
+
+/// Bookkeeping of tasks submitted by an [`MSMClient`].
+#[derive(Default)]
+pub(super) struct TaskQueue {
+    /// Serializes pushing of tasks: initialization, push, label read and data transfer.
+    submit: Mutex<()>,
+    state: Mutex<TaskState>,
+}
+
+#[derive(Default)]
+struct TaskState {
+    /// Labels of submitted tasks whose results are not taken yet.
+    submitted: HashSet<u32>,
+    /// Labels of tasks whose handles were dropped, their results are discarded.
+    abandoned: HashSet<u32>,
+    /// Popped results which are not taken by their handles yet.
+    completed: HashMap<u32, MSMResult>,
+}
+
+/// A handle of a submitted MSM task.
+///
+/// The result is taken with [`MsmTaskHandle::try_result`] or [`MsmTaskHandle::wait`].
+/// If the handle is dropped before, the result is discarded when it arrives.
+pub struct MsmTaskHandle<'a> {
+    client: &'a MSMClient,
+    label: u32,
+    taken: bool,
+}
+
+impl MsmTaskHandle<'_> {
+    /// The label the core assigned to the task.
+    pub fn label(&self) -> u32 {
+        self.label
+    }
+
+    /// Returns the result if the task is completed, without waiting.
+    ///
+    /// Results of other tasks which are ready are popped from the core
+    /// and kept for their handles.
+    pub fn try_result(&mut self) -> Result<Option<MSMResult>> {
+        if self.taken {
+            return Ok(None);
+        }
+        let mut state = self.client.tasks.state.lock().unwrap();
+        self.client.collect_results(&mut state)?;
+        let result = state.completed.remove(&self.label);
+        if result.is_some() {
+            state.submitted.remove(&self.label);
+            self.taken = true;
+        }
+        Ok(result)
+    }
+
+    /// Waits for the task to complete and returns its result.
+    pub fn wait(mut self) -> Result<MSMResult> {
+        loop {
+            if let Some(result) = self.try_result()? {
+                return Ok(result);
+            }
+            hint::spin_loop();
+        }
+    }
+
+    fn is_completed(&self) -> bool {
+        let state = self.client.tasks.state.lock().unwrap();
+        state.completed.contains_key(&self.label)
+    }
+}
+
+impl Drop for MsmTaskHandle<'_> {
+    fn drop(&mut self) {
+        if self.taken {
+            return;
+        }
+        let mut state = self.client.tasks.state.lock().unwrap();
+        if state.completed.remove(&self.label).is_some() {
+            state.submitted.remove(&self.label);
+        } else {
+            state.abandoned.insert(self.label);
+        }
+    }
+}
+
+impl MSMClient {
+    /// Pushes a task to the queue of the core, sends its data and returns a handle of the task.
+    ///
+    /// The call returns once the data is sent, the computation goes on in the background.
+    /// Returns [`DriverClientError::TaskTooLarge`] if the input doesn't fit into one task,
+    /// see [`MSMClient::run`] for larger MSMs.
+    ///
+    /// If sending the data fails, the task is already pushed with a part of its input.
+    /// It is abandoned, so a result with its label is discarded instead of being reported
+    /// as a mismatch, but the core waits for the rest of the input and has to be reset
+    /// with [`DriverClient::reset`] before other tasks are submitted.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use ingo_blaze::{driver_client::*, ingo_msm::*};
+    /// # fn example(client: &MSMClient, input: MSMInput) -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut task = client.submit(input)?;
+    /// // do some work on the CPU
+    /// if let Some(result) = task.try_result()? {
+    ///     // the task is already completed
+    /// }
+    /// let result = task.wait()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn submit(&self, input: MSMInput) -> Result<MsmTaskHandle<'_>> {
+        let max_nof_elements = self.msm_cfg.max_nof_elements;
+        if input.params.nof_elements as usize > max_nof_elements {
+            return Err(DriverClientError::TaskTooLarge {
+                nof_elements: input.params.nof_elements as usize,
+                max_nof_elements,
+            });
+        }
+
+        let _submit = self.tasks.submit.lock().unwrap();
+        self.initialize(input.params)?;
+        self.start_process(None)?;
+        let label = self.task_label()?;
+        log::debug!("Pushed task with label: {}", label);
+        {
+            let mut state = self.tasks.state.lock().unwrap();
+            state.submitted.insert(label);
+            state.abandoned.remove(&label);
+        }
+        if let Err(e) = self.set_data(input) {
+            self.tasks.state.lock().unwrap().abandoned.insert(label);
+            return Err(e);
+        }
+
+        Ok(MsmTaskHandle {
+            client: self,
+            label,
+            taken: false,
+        })
+    }
+
+    /// Runs a batch of MSMs back-to-back through the task queue of the core.
+    ///
+    /// Every task is pushed right after the data of the previous one is sent,
+    /// so DMA of task `N + 1` overlaps with computation of task `N`. Results which are ready
+    /// are popped between submissions to keep the result queue from filling up.
+    /// Results are matched to inputs by task labels and returned in the order of `inputs`.
+    ///
+    /// The core completes tasks in order, so [`DriverClientError::MissingResult`] is returned
+    /// if a later task of the batch completes before an earlier one. A result with a label
+    /// which was never submitted is reported as [`DriverClientError::ResultLabelMismatch`].
+    pub fn run_batch(&self, inputs: &[MSMInput]) -> Result<Vec<MSMResult>> {
+        let mut handles = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            handles.push(self.submit(*input)?);
+            let mut state = self.tasks.state.lock().unwrap();
+            self.collect_results(&mut state)?;
+        }
+
+        let mut results = Vec::with_capacity(inputs.len());
+        for i in 0..handles.len() {
+            let result = loop {
+                if let Some(result) = handles[i].try_result()? {
+                    break result;
+                }
+                if handles[i + 1..].iter().any(|h| h.is_completed()) {
+                    return Err(DriverClientError::MissingResult {
+                        label: handles[i].label,
+                    });
+                }
+                hint::spin_loop();
+            };
+            results.push(result);
+        }
+        Ok(results)
+    }
+
+    /// Pops all ready results from the core into `state`.
+    fn collect_results(&self, state: &mut TaskState) -> Result<()> {
+        loop {
+            if !self.is_result_valid()? {
+                return Ok(());
+            }
+
+            let result = self.result(None)?.unwrap();
+            let label = result.result_label;
+            log::debug!("Popped result with label: {}", label);
+            if !state.submitted.contains(&label) {
+                return Err(DriverClientError::ResultLabelMismatch { label });
+            }
+            if state.abandoned.remove(&label) {
+                state.submitted.remove(&label);
+            } else {
+                state.completed.insert(label, result);
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! Verification of bases uploaded into HBM.
+//!
+//! Bases are compared block by block: either data read back from HBM is hashed and compared
+//! with the hashes of the uploaded data, or only a sample of blocks is read back.
+//! Hashes of resident base sets are kept by the client, so [`MSMClient::verify_resident_bases`]
+//! can check them long after the host copy is gone.
+use super::{msm_api::*, msm_hbm::HbmBaseSet};
+use crate::error::*;
+
+use rayon::prelude::*;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::Hasher,
+    ops::Range,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// The size of a block of HBM data which is hashed and compared as a whole.
+pub const HBM_VERIFY_BLOCK_SIZE: usize = 1 << 20;
+
+/// How data uploaded into HBM is verified.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum HbmVerify {
+    /// Data is not read back.
+    #[default]
+    Off,
+    /// All data is read back and compared.
+    Full,
+    /// `nof_blocks` blocks of [`HBM_VERIFY_BLOCK_SIZE`] spread over the data are read back
+    /// and compared, the blocks are chosen anew on every check.
+    Sampled { nof_blocks: usize },
+}
+
+/// A range of a resident base set whose data in HBM doesn't match the uploaded data.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HbmMismatch {
+    pub name: String,
+    /// HBM addresses of the mismatching blocks.
+    pub range: Range<u64>,
+}
+
+/// Hashes of consecutive blocks of `data`.
+pub(super) fn block_hashes(data: &[u8]) -> Vec<u64> {
+    data.par_chunks(HBM_VERIFY_BLOCK_SIZE)
+        .map(block_hash)
+        .collect()
+}
+
+fn block_hash(block: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(block);
+    hasher.finish()
+}
+
+/// Indices of blocks out of `nof_blocks` which are checked in the given mode.
+fn select_blocks(nof_blocks: usize, mode: HbmVerify) -> Vec<usize> {
+    match mode {
+        HbmVerify::Off => vec![],
+        HbmVerify::Sampled {
+            nof_blocks: samples,
+        } if samples < nof_blocks => {
+            // Evenly spread blocks starting from a varying offset,
+            // so repeated checks cover different blocks.
+            let stride = nof_blocks / samples.max(1);
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.subsec_nanos() as usize)
+                .unwrap_or(0);
+            (0..samples.max(1))
+                .map(|i| seed % stride + i * stride)
+                .collect()
+        }
+        _ => (0..nof_blocks).collect(),
+    }
+}
+
+impl MSMClient {
+    /// Sets how data is verified after it is uploaded into HBM by `load_data_to_hbm`
+    /// and `load_base_set`. Verification is off by default.
+    pub fn with_hbm_verify(mut self, verify: HbmVerify) -> Self {
+        self.hbm_verify = verify;
+        self
+    }
+
+    /// Compares data in HBM at `addr` with `data` in the given mode.
+    /// Returns HBM ranges of mismatching blocks, adjacent blocks are merged.
+    pub fn verify_hbm(&self, data: &[u8], addr: u64, mode: HbmVerify) -> Result<Vec<Range<u64>>> {
+        let blocks = select_blocks(data.len().div_ceil(HBM_VERIFY_BLOCK_SIZE), mode);
+        self.compare_blocks(addr, data.len(), &blocks, |i| {
+            let start = i * HBM_VERIFY_BLOCK_SIZE;
+            let end = (start + HBM_VERIFY_BLOCK_SIZE).min(data.len());
+            block_hash(&data[start..end])
+        })
+    }
+
+    /// Checks all resident base sets against hashes taken when they were loaded.
+    /// Returns mismatching ranges of every set, an empty list means all checked blocks are intact.
+    pub fn verify_resident_bases(&self, mode: HbmVerify) -> Result<Vec<HbmMismatch>> {
+        let mut mismatches = vec![];
+        for set in self.base_sets() {
+            for range in self.verify_base_set(&set, mode)? {
+                log::warn!("Base set {:?} is corrupted in {:#X?}", set.name, range);
+                mismatches.push(HbmMismatch {
+                    name: set.name.clone(),
+                    range,
+                });
+            }
+        }
+        Ok(mismatches)
+    }
+
+    fn verify_base_set(&self, set: &HbmBaseSet, mode: HbmVerify) -> Result<Vec<Range<u64>>> {
+        // Scratch regions of running tasks have no hashes.
+        let blocks = select_blocks(set.block_hashes.len(), mode);
+        self.compare_blocks(set.addr, set.size as usize, &blocks, |i| {
+            set.block_hashes[i]
+        })
+    }
+
+    /// Verifies an upload according to the mode of the client,
+    /// returns [`DriverClientError::HbmDataMismatch`] if the data doesn't match.
+    pub(super) fn check_upload(&self, data: &[u8], addr: u64) -> Result<()> {
+        let ranges = self.verify_hbm(data, addr, self.hbm_verify)?;
+        if ranges.is_empty() {
+            return Ok(());
+        }
+        Err(DriverClientError::HbmDataMismatch { ranges })
+    }
+
+    fn compare_blocks(
+        &self,
+        addr: u64,
+        len: usize,
+        blocks: &[usize],
+        expected: impl Fn(usize) -> u64,
+    ) -> Result<Vec<Range<u64>>> {
+        let mut ranges: Vec<Range<u64>> = vec![];
+        let mut buf = vec![0; HBM_VERIFY_BLOCK_SIZE.min(len)];
+        for &i in blocks {
+            let start = i * HBM_VERIFY_BLOCK_SIZE;
+            let end = (start + HBM_VERIFY_BLOCK_SIZE).min(len);
+            let block = &mut buf[..end - start];
+            self.driver_client
+                .dma_read_striped(addr, start as u64, block)?;
+            if block_hash(block) == expected(i) {
+                continue;
+            }
+
+            let range = addr + start as u64..addr + end as u64;
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn select_verified_blocks() {
+        assert!(select_blocks(10, HbmVerify::Off).is_empty());
+        assert_eq!(select_blocks(4, HbmVerify::Full), [0, 1, 2, 3]);
+        assert_eq!(
+            select_blocks(4, HbmVerify::Sampled { nof_blocks: 8 }),
+            [0, 1, 2, 3]
+        );
+
+        let blocks = select_blocks(100, HbmVerify::Sampled { nof_blocks: 10 });
+        assert_eq!(blocks.len(), 10);
+        assert!(blocks.windows(2).all(|w| w[1] - w[0] == 10));
+        assert!(blocks[9] < 100);
+
+        let data = vec![7; HBM_VERIFY_BLOCK_SIZE + 1];
+        let hashes = block_hashes(&data);
+        assert_eq!(hashes.len(), 2);
+        assert_eq!(hashes[0], block_hash(&data[..HBM_VERIFY_BLOCK_SIZE]));
+        assert_ne!(hashes[0], hashes[1]);
+    }
+}
@@ -1,4 +1,6 @@
 mod ntt_api;
+#[cfg(feature = "async")]
+mod ntt_async;
 mod ntt_data;
 mod ntt_hw_code;
 
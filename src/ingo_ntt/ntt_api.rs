@@ -3,7 +3,7 @@ use super::{
     ntt_hw_code::*,
 };
 use crate::{driver_client::*, error::*};
-use std::{fmt::Debug, os::unix::fs::FileExt};
+use std::fmt::Debug;
 
 pub enum NTT {
     Ntt,
@@ -17,12 +17,12 @@ pub struct NTTClient {
 pub struct NttInit {}
 
 #[derive(Debug, Clone)]
-pub struct NTTInput {
+pub struct NTTInput<'a> {
     pub buf_host: usize,
-    pub data: Vec<u8>,
+    pub data: &'a [u8],
 }
 
-impl DriverPrimitive<NTT, NttInit, NTTInput, Vec<u8>> for NTTClient {
+impl<'a> DriverPrimitive<NTT, NttInit, NTTInput<'a>, Vec<u8>> for NTTClient {
     fn new(_ptype: NTT, dclient: DriverClient) -> Self {
         NTTClient {
             ntt_cfg: NTTConfig::ntt_cfg(),
@@ -69,57 +69,72 @@ impl DriverPrimitive<NTT, NttInit, NTTInput, Vec<u8>> for NTTClient {
         )
     }
 
-    fn set_data(&self, input: NTTInput) -> Result<()> {
-        let data_banks = NTTBanks::preprocess(input.data);
-
-        data_banks
-            .banks
-            .into_iter()
-            .enumerate()
-            .try_for_each(|(i, data_in)| {
-                let offset = self.ntt_cfg.ntt_bank_start_addr(i, input.buf_host);
-                self.driver_client.dma_write(
-                    self.driver_client.cfg.dma_baseaddr,
-                    offset,
-                    data_in.as_slice(),
-                )
-            })
+    fn set_data(&self, input: NTTInput<'a>) -> Result<()> {
+        let mut data_banks = self.bank_buffers()?;
+        NTTBanks::preprocess_into(input.data, &mut data_banks);
+
+        // Small banks are written by one vectored write, which saves a syscall per bank.
+        // Banks large enough to be striped are written one by one across all DMA channels,
+        // a single bank then keeps every channel busy and the vectored write would use only one.
+        let bank_len = data_banks.first().map_or(0, |bank| bank.len());
+        if !self.driver_client.is_write_striped(bank_len) {
+            let segments: Vec<(u64, &[u8])> = data_banks
+                .iter()
+                .enumerate()
+                .map(|(i, data_in)| (self.bank_addr(i, input.buf_host), &data_in[..]))
+                .collect();
+            return self.driver_client.dma_write_vectored(&segments);
+        }
+        data_banks.iter().enumerate().try_for_each(|(i, data_in)| {
+            self.driver_client
+                .dma_write_striped(self.bank_addr(i, input.buf_host), 0u64, data_in)
+        })
     }
 
     fn wait_result(&self) -> Result<()> {
-        let mut result_valid = [0, 0, 0, 0];
-        let mut done = false;
         log::debug!("Waiting ready signal from offset: XHBM_SS_CONTROL_ADDR_AP_CTRL");
-        while !done {
-            self.driver_client
-                .ctrl
-                .read_exact_at(
-                    &mut result_valid,
-                    self.driver_client.cfg.ctrl_baseaddr
-                        + INGO_NTT_SUPER_PROGRAM_ADDR::XHBM_SS_CONTROL_ADDR_AP_CTRL as u64,
-                )
-                .map_err(|e| DriverClientError::ReadError {
-                    offset: "XHBM_SS_CONTROL_ADDR_AP_CTRL".to_string(),
-                    source: e,
-                })?;
-            done = (result_valid[0] & 0x2) == 0x2;
-        }
+        while !self.is_done()? {}
         Ok(())
     }
 
     fn result(&self, buf_num: Option<usize>) -> Result<Option<Vec<u8>>> {
-        let mut res_banks: NTTBanks = Default::default();
-        for i in 0..NOF_BANKS {
-            let offset = self.ntt_cfg.ntt_bank_start_addr(i, buf_num.unwrap());
-            res_banks.banks[i] = vec![0; NTTConfig::NTT_BUFFER_SIZE];
-            self.driver_client.dma_read(
-                self.driver_client.cfg.dma_baseaddr,
-                offset,
-                &mut res_banks.banks[i],
-            )?;
+        let mut res_banks = self.bank_buffers()?;
+        for (i, bank) in res_banks.iter_mut().enumerate() {
+            self.driver_client
+                .dma_read_striped(self.bank_addr(i, buf_num.unwrap()), 0u64, bank)?;
         }
 
-        let res = res_banks.postprocess();
+        let res = NTTBanks::postprocess_banks(&res_banks);
         Ok(Some(res))
     }
 }
+
+impl NTTClient {
+    /// Checks the done bit of the control register of the core.
+    pub(super) fn is_done(&self) -> Result<bool> {
+        let mut result_valid = [0, 0, 0, 0];
+        self.driver_client.ctrl_read(
+            self.driver_client.cfg.ctrl_baseaddr,
+            INGO_NTT_SUPER_PROGRAM_ADDR::XHBM_SS_CONTROL_ADDR_AP_CTRL,
+            &mut result_valid,
+        )?;
+        Ok((result_valid[0] & 0x2) == 0x2)
+    }
+
+    /// The absolute DMA address of a bank of the buffer `buf_num`.
+    pub(super) fn bank_addr(&self, bank_num: usize, buf_num: usize) -> u64 {
+        self.driver_client.cfg.dma_baseaddr + self.ntt_cfg.ntt_bank_start_addr(bank_num, buf_num)
+    }
+
+    /// Takes host buffers for all banks from the DMA buffer pool,
+    /// they are reused by the next tasks after being dropped.
+    pub(super) fn bank_buffers(&self) -> Result<Vec<PooledDmaBuffer<'_>>> {
+        (0..NOF_BANKS)
+            .map(|_| {
+                self.driver_client
+                    .buffer_pool
+                    .acquire(NTTConfig::NTT_BUFFER_SIZE)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,44 @@
+//! Async versions of the data transfer and result methods of [`NTTClient`].
+//!
+//! Banks live in HBM, so a dropped future leaves the device consistent:
+//! a dropped [`NTTClient::set_data_async`] leaves the buffer partially written
+//! and it must be set again before the task is started.
+use super::{ntt_api::*, ntt_data::NTTBanks};
+use crate::{driver_client::*, error::*};
+
+impl NTTClient {
+    /// Async version of [`DriverPrimitive::set_data`].
+    ///
+    /// Data is split into banks on the calling thread, banks are written piece by piece between polls.
+    pub async fn set_data_async(&self, input: NTTInput<'_>) -> Result<()> {
+        let mut data_banks = self.bank_buffers()?;
+        NTTBanks::preprocess_into(input.data, &mut data_banks);
+
+        let segments: Vec<(u64, &[u8])> = data_banks
+            .iter()
+            .enumerate()
+            .map(|(i, bank)| (self.bank_addr(i, input.buf_host), &bank[..]))
+            .collect();
+        WriteSegments::new(&self.driver_client)
+            .memory(&segments)
+            .await
+    }
+
+    /// Async version of [`DriverPrimitive::wait_result`], polls the control register of the core.
+    pub async fn wait_result_async(&self) -> Result<()> {
+        poll_device(|| Ok(self.is_done()?.then_some(()))).await
+    }
+
+    /// Async version of [`DriverPrimitive::result`], banks are read piece by piece between polls.
+    pub async fn result_async(&self, buf_num: usize) -> Result<Option<Vec<u8>>> {
+        let mut res_banks = self.bank_buffers()?;
+        let segments: Vec<(u64, &mut [u8])> = res_banks
+            .iter_mut()
+            .enumerate()
+            .map(|(i, bank)| (self.bank_addr(i, buf_num), &mut bank[..]))
+            .collect();
+        ReadSegments::new(&self.driver_client, segments).await?;
+
+        Ok(Some(NTTBanks::postprocess_banks(&res_banks)))
+    }
+}
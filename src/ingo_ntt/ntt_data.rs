@@ -56,10 +56,9 @@ impl NTTConfig {
     }
 }
 
-#[derive(Debug, Clone, Default)]
-pub(super) struct NTTBanks {
-    pub banks: [Vec<u8>; NOF_BANKS],
-}
+/// Layout of a single buffer split between HBM banks.
+/// The client works with pooled bank buffers, so the banks are passed in by the caller.
+pub(super) struct NTTBanks;
 
 impl NTTBanks {
     const NTT_SIZE: usize = 134217728; // Size of NTT = 2**27
@@ -77,12 +76,10 @@ impl NTTBanks {
     const NTT_NOF_SUBNTT: usize = 8;
     const NTT_NOF_ROW: usize = 64;
 
-    pub(super) fn preprocess(input: Vec<u8>) -> Self {
+    /// Splits the input vector between banks, each bank has to be of size `input.len() / NOF_BANKS`.
+    pub(super) fn preprocess_into<B: AsMut<[u8]>>(input: &[u8], banks: &mut [B]) {
         log::info!("Start preparing the input vector before NTT");
-        let mut banks: Vec<Vec<u8>> = Vec::with_capacity(NOF_BANKS);
-        for _ in 0..NOF_BANKS {
-            banks.push(Default::default());
-        }
+        let mut bank_offset = [0usize; NOF_BANKS];
         let mut addr = 0;
         for group in 0..Self::NTT_NOF_GROUPS {
             for _ in 0..Self::NTT_NOF_SLICE {
@@ -93,7 +90,10 @@ impl NTTBanks {
                                 for bank_num in cores.into_iter() {
                                     let buf: &[u8] = &input[(bank_num % 8 + row * 8) * 32 + addr
                                         ..(bank_num % 8 + row * 8 + 1) * 32 + addr];
-                                    banks[bank_num].extend_from_slice(buf);
+                                    let offset = bank_offset[bank_num];
+                                    banks[bank_num].as_mut()[offset..offset + 32]
+                                        .copy_from_slice(buf);
+                                    bank_offset[bank_num] += 32;
                                 }
                             }
                             addr +=
@@ -104,13 +104,10 @@ impl NTTBanks {
             }
             log::trace!("Group {} is ready", group)
         }
-
-        NTTBanks {
-            banks: banks.try_into().unwrap(),
-        }
     }
 
-    pub(super) fn postprocess(&self) -> Vec<u8> {
+    /// Merges banks read from HBM into the result vector.
+    pub(super) fn postprocess_banks<B: AsRef<[u8]>>(banks: &[B]) -> Vec<u8> {
         log::info!("Start processing the result after NTT");
         let mut res = vec![0u8; Self::NTT_SIZE * Self::NTT_WORD_SIZE];
         log::debug!("Allocate vector of size: {}", res.len());
@@ -138,7 +135,7 @@ impl NTTBanks {
                                 for bank_num in cores[icore].into_iter() {
                                     let addr = 512 * isubntt + i;
                                     res[addr * 32..(addr + 1) * 32].copy_from_slice(
-                                        &self.banks[bank_num]
+                                        &banks[bank_num].as_ref()
                                             [bank_offset[bank_num]..bank_offset[bank_num] + 32],
                                     );
                                     bank_offset[bank_num] += 32;
@@ -170,10 +167,11 @@ mod tests {
         let mut f = File::open(&fname).expect("no file found");
         let mut in_vec: Vec<u8> = Default::default();
         let _ = f.read_to_end(&mut in_vec);
-        let got = NTTBanks::preprocess(in_vec);
+        let mut got = vec![vec![0u8; in_vec.len() / NOF_BANKS]; NOF_BANKS];
+        NTTBanks::preprocess_into(&in_vec, &mut got);
 
         for (i, expb) in exp.iter().enumerate().take(16) {
-            if got.banks[i].eq(expb) {
+            if got[i].eq(expb) {
                 println!("Bank {} is correct", i);
             }
         }
@@ -199,10 +197,7 @@ mod tests {
         let fdir = env::var("FDIR").unwrap();
         let fname = env::var("FNAME").unwrap();
         let in_banks: Vec<Vec<u8>> = already_postprocess(fdir);
-        let ntt_banks = NTTBanks {
-            banks: in_banks.try_into().unwrap(),
-        };
-        let got = ntt_banks.postprocess();
+        let got = NTTBanks::postprocess_banks(&in_banks);
         println!("Got result of size: {}", got.len());
 
         let mut f = File::open(&fname).expect("no file found");
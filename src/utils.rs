@@ -1,9 +1,11 @@
 use anyhow;
 use std::{
     fs::{File, OpenOptions},
-    io::{Error, Read},
-    os::unix::prelude::OpenOptionsExt,
-    thread, time,
+    io::{Error, ErrorKind, Read},
+    ops::Deref,
+    os::unix::{fs::FileExt, io::AsRawFd, prelude::OpenOptionsExt},
+    path::Path,
+    ptr, slice, thread, time,
 };
 
 #[macro_export]
@@ -37,7 +39,7 @@ macro_rules! setter_log {
 }
 
 #[repr(u8)]
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq, Eq, Clone, Copy)]
 pub enum AccessFlags {
     RdMode = 0,   // rdonly channel
     WrMode = 1,   // wronly channel
@@ -58,6 +60,10 @@ impl AccessFlags {
 // ==== read/write ====
 
 pub fn open_channel(path: &str, mode: AccessFlags) -> std::fs::File {
+    try_open_channel(path, mode).unwrap()
+}
+
+pub fn try_open_channel(path: &str, mode: AccessFlags) -> Result<std::fs::File, Error> {
     let mut options = OpenOptions::new();
     if mode == AccessFlags::RdwrMode || mode == AccessFlags::RdMode {
         options.read(true);
@@ -71,7 +77,147 @@ pub fn open_channel(path: &str, mode: AccessFlags) -> std::fs::File {
         options.custom_flags(libc::O_SYNC);
         // options.custom_flags(libc::O_RDWR);
     }
-    options.open(path).unwrap()
+    options.open(path)
+}
+
+/// Writes all `bufs` one after another starting from `offset` of the file using `pwritev`.
+/// The number of syscalls is limited by partial writes and `IOV_MAX` only.
+/// If the device does not support vectored writes, the buffers are written one by one.
+pub fn write_all_vectored_at(file: &File, bufs: &[&[u8]], offset: u64) -> Result<(), Error> {
+    const IOV_MAX: usize = 1024;
+
+    let mut offset = offset;
+    let mut bufs: Vec<&[u8]> = bufs.iter().copied().filter(|b| !b.is_empty()).collect();
+    let mut first = 0;
+    while first < bufs.len() {
+        let iov: Vec<libc::iovec> = bufs[first..]
+            .iter()
+            .take(IOV_MAX)
+            .map(|b| libc::iovec {
+                iov_base: b.as_ptr() as *mut libc::c_void,
+                iov_len: b.len(),
+            })
+            .collect();
+        let ret = unsafe {
+            libc::pwritev(
+                file.as_raw_fd(),
+                iov.as_ptr(),
+                iov.len() as libc::c_int,
+                offset as libc::off_t,
+            )
+        };
+        if ret < 0 {
+            let err = Error::last_os_error();
+            match err.raw_os_error() {
+                Some(libc::EINTR) => continue,
+                Some(libc::EINVAL) | Some(libc::ENOSYS) | Some(libc::EOPNOTSUPP) => {
+                    log::debug!("Vectored write is not supported, fallback to pwrite");
+                    for b in bufs[first..].iter() {
+                        file.write_all_at(b, offset)?;
+                        offset += b.len() as u64;
+                    }
+                    return Ok(());
+                }
+                _ => return Err(err),
+            }
+        }
+        if ret == 0 {
+            return Err(Error::new(
+                ErrorKind::WriteZero,
+                "failed to write whole buffer",
+            ));
+        }
+
+        // Skip fully written buffers and cut the partially written one.
+        let mut written = ret as usize;
+        offset += written as u64;
+        while first < bufs.len() && written >= bufs[first].len() {
+            written -= bufs[first].len();
+            first += 1;
+        }
+        if written > 0 {
+            bufs[first] = &bufs[first][written..];
+        }
+    }
+    Ok(())
+}
+
+/// A read-only memory mapping of a whole file.
+///
+/// Pages are loaded on first access, so large inputs (SRS points, witness scalars) can be fed to the
+/// device without reading them into RAM, and [`MappedFile::release`] drops pages which are not needed anymore.
+pub struct MappedFile {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+// The mapping is read-only and valid for the whole lifetime of the struct.
+unsafe impl Send for MappedFile {}
+unsafe impl Sync for MappedFile {}
+
+impl MappedFile {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::open(path.as_ref())?;
+        let len = file.metadata()?.len() as usize;
+        log::debug!("Map file {:?} of size {}", path.as_ref(), len);
+        if len == 0 {
+            return Ok(MappedFile {
+                ptr: ptr::null_mut(),
+                len,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        unsafe { libc::madvise(ptr, len, libc::MADV_SEQUENTIAL) };
+        Ok(MappedFile { ptr, len })
+    }
+
+    /// Drops loaded pages which lie completely inside `start..end` of the file.
+    /// The content stays available, it is read from the file again on the next access.
+    pub fn release(&self, start: usize, end: usize) {
+        const PAGE_SIZE: usize = 0x1000;
+        let start = start.div_ceil(PAGE_SIZE) * PAGE_SIZE;
+        let end = end.min(self.len) / PAGE_SIZE * PAGE_SIZE;
+        if start < end {
+            unsafe { libc::madvise(self.ptr.add(start), end - start, libc::MADV_DONTNEED) };
+        }
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len == 0 {
+            return &[];
+        }
+        unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
+    }
+}
+
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl Drop for MappedFile {
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { libc::munmap(self.ptr, self.len) };
+        }
+    }
 }
 
 pub fn read_binary_file(path: &str) -> Result<Vec<u8>, Error> {
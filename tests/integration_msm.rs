@@ -1,4 +1,4 @@
-use ingo_blaze::{driver_client::*, ingo_msm::*, utils::*};
+use ingo_blaze::{driver_client::*, error::DriverClientError, ingo_msm::*, utils::*};
 use num_traits::Pow;
 use std::{
     env,
@@ -28,8 +28,10 @@ fn load_msm_binary_test() -> Result<(), Box<dyn std::error::Error>> {
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: false,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
             curve: Curve::BLS381,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -68,6 +70,7 @@ fn load_msm_binary_test() -> Result<(), Box<dyn std::error::Error>> {
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
 
     driver.initialize(msm_params)?;
@@ -75,8 +78,8 @@ fn load_msm_binary_test() -> Result<(), Box<dyn std::error::Error>> {
 
     log::info!("Starting to calculate MSM: ");
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     driver.wait_result()?;
@@ -105,8 +108,10 @@ fn msm_bls12_377_test() -> Result<(), Box<dyn std::error::Error>> {
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: false,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
             curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -125,14 +130,15 @@ fn msm_bls12_377_test() -> Result<(), Box<dyn std::error::Error>> {
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
 
     driver.initialize(msm_params)?;
     driver.start_process(None)?;
     log::info!("Starting to calculate MSM: ");
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     driver.wait_result()?;
@@ -146,6 +152,177 @@ fn msm_bls12_377_test() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+#[test]
+fn msm_bls12_377_streaming_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+    let msm_size = env::var("MSM_SIZE")
+        .unwrap_or_else(|_| 65536.to_string())
+        .parse::<u32>()
+        .unwrap();
+
+    log::info!("Create Driver API instance");
+    let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+    let driver = MSMClient::new(
+        MSMInit {
+            mem_type: PointMemoryType::DMA,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
+            curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        },
+        dclient,
+    );
+    driver.is_msm_engine_ready()?;
+
+    let (points, scalars, msm_result, results) =
+        msm::input_generator_bls12_377(msm_size as usize, PRECOMPUTE_FACTOR_BASE);
+    let msm_params = MSMParams {
+        nof_elements: msm_size,
+        hbm_point_addr: None,
+        tuning: None,
+    };
+
+    driver.initialize(msm_params)?;
+    driver.start_process(None)?;
+    log::info!("Starting to stream MSM input: ");
+    let mut last = None;
+    driver.set_data_streaming(
+        MSMInput {
+            points: Some(&points),
+            scalars: &scalars,
+            params: msm_params,
+        },
+        &MSMStreamConfig::default(),
+        |progress| {
+            log::debug!("{:?}", progress);
+            assert!(progress.consumed <= progress.sent);
+            last = Some(progress);
+        },
+    )?;
+    assert_eq!(last.unwrap().sent, msm_size as usize);
+    driver.wait_result()?;
+    let mres = driver.result(None).unwrap().unwrap();
+    let (is_on_curve, is_eq) =
+        msm::result_check_bls12_377(mres.result, msm_result, results, msm_size as usize);
+    assert!(is_on_curve);
+    assert!(is_eq);
+    Ok(())
+}
+
+#[test]
+fn msm_bls12_377_batch_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+    let msm_size = env::var("MSM_SIZE")
+        .unwrap_or_else(|_| 8192.to_string())
+        .parse::<u32>()
+        .unwrap();
+    let batch_size = 4;
+
+    log::info!("Create Driver API instance");
+    let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+    let driver = MSMClient::new(
+        MSMInit {
+            mem_type: PointMemoryType::DMA,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
+            curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        },
+        dclient,
+    );
+    driver.is_msm_engine_ready()?;
+
+    let inputs_data: Vec<_> = (0..batch_size)
+        .map(|_| msm::input_generator_bls12_377(msm_size as usize, PRECOMPUTE_FACTOR_BASE))
+        .collect();
+    let inputs: Vec<MSMInput> = inputs_data
+        .iter()
+        .map(|(points, scalars, _, _)| MSMInput {
+            points: Some(points),
+            scalars,
+            params: MSMParams {
+                nof_elements: msm_size,
+                hbm_point_addr: None,
+                tuning: None,
+            },
+        })
+        .collect();
+
+    log::info!("Starting to calculate batch of {} MSMs: ", batch_size);
+    let mres = driver.run_batch(&inputs)?;
+    assert_eq!(mres.len(), batch_size);
+    let stats = driver.last_task_stats()?;
+    log::info!(
+        "Last task: {:?}, EC adders utilization {:.1}%, wall time {:?}",
+        stats,
+        stats.ec_adder_utilization(),
+        stats.wall_time()
+    );
+    for (mres, (_, _, msm_result, results)) in mres.into_iter().zip(inputs_data) {
+        let (is_on_curve, is_eq) =
+            msm::result_check_bls12_377(mres.result, msm_result, results, msm_size as usize);
+        assert!(is_on_curve);
+        assert!(is_eq);
+    }
+    Ok(())
+}
+
+#[test]
+fn msm_bls12_377_tuning_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+    let msm_size = 8192;
+
+    let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+    let driver = MSMClient::new(
+        MSMInit {
+            mem_type: PointMemoryType::DMA,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
+            curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        },
+        dclient,
+    );
+    let (points, scalars, msm_result, results) =
+        msm::input_generator_bls12_377(msm_size, PRECOMPUTE_FACTOR_BASE);
+
+    for dma_chunk_size in [512, 1000, 4096] {
+        log::info!("Running MSM with DMA chunks of {} elements", dma_chunk_size);
+        let mres = driver.run(MSMInput {
+            points: Some(&points),
+            scalars: &scalars,
+            params: MSMParams {
+                nof_elements: msm_size as u32,
+                hbm_point_addr: None,
+                tuning: Some(MSMTuning {
+                    dma_chunk_size,
+                    ..MSMTuning::default()
+                }),
+            },
+        })?;
+        let (is_on_curve, is_eq) =
+            msm::result_check_bls12_377(mres.result, msm_result, results.clone(), msm_size);
+        assert!(is_on_curve && is_eq);
+    }
+
+    let invalid = driver.initialize(MSMParams {
+        nof_elements: msm_size as u32,
+        hbm_point_addr: None,
+        tuning: Some(MSMTuning {
+            dma_chunk_size: 1,
+            ..MSMTuning::default()
+        }),
+    });
+    assert!(matches!(
+        invalid,
+        Err(DriverClientError::InvalidTuning { .. })
+    ));
+    Ok(())
+}
+
 #[test]
 fn msm_bls12_381_test() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::try_init().expect("Invalid logger initialisation");
@@ -163,8 +340,10 @@ fn msm_bls12_381_test() -> Result<(), Box<dyn std::error::Error>> {
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: false,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
             curve: Curve::BLS381,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -181,14 +360,15 @@ fn msm_bls12_381_test() -> Result<(), Box<dyn std::error::Error>> {
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
 
     driver.initialize(msm_params)?;
     driver.start_process(None)?;
     log::info!("Starting to calculate MSM: ");
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     driver.driver_client.firewalls_status();
@@ -220,8 +400,10 @@ fn msm_bn254_test() -> Result<(), Box<dyn std::error::Error>> {
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: false,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
             curve: Curve::BN254,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -240,14 +422,15 @@ fn msm_bn254_test() -> Result<(), Box<dyn std::error::Error>> {
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
 
     driver.initialize(msm_params)?;
     driver.start_process(None)?;
     log::info!("Starting to calculate MSM: ");
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     driver.wait_result()?;
@@ -281,6 +464,31 @@ impl Display for RunResults {
     }
 }
 
+#[test]
+fn msm_bn254_g2_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+
+    log::info!("Create Driver API instance");
+    let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+    // G2 results don't fit into the result registers of the core.
+    let driver = MSMClient::try_new(
+        MSMInit {
+            mem_type: PointMemoryType::DMA,
+            precompute_factor: PRECOMPUTE_FACTOR_BASE,
+            curve: Curve::BN254,
+            group: MSMGroup::G2,
+            tuning: MSMTuning::default(),
+        },
+        dclient,
+    );
+    assert!(matches!(
+        driver,
+        Err(DriverClientError::UnsupportedGroup { .. })
+    ));
+    Ok(())
+}
+
 #[test]
 fn msm_bls12_377_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::try_init().expect("Invalid logger initialisation");
@@ -311,8 +519,10 @@ fn msm_bls12_377_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let driver = MSMClient::new(
             MSMInit {
                 mem_type: PointMemoryType::DMA,
-                is_precompute: true,
+                precompute_factor: PRECOMPUTE_FACTOR,
                 curve: Curve::BLS377,
+                group: MSMGroup::G1,
+                tuning: MSMTuning::default(),
             },
             dclient,
         );
@@ -327,6 +537,7 @@ fn msm_bls12_377_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let msm_params = MSMParams {
             nof_elements: msm_size as u32,
             hbm_point_addr: None,
+            tuning: None,
         };
         driver.initialize(msm_params)?;
         driver.start_process(None)?;
@@ -338,8 +549,8 @@ fn msm_bls12_377_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let start_set_data = Instant::now();
         let start_full = Instant::now();
         driver.set_data(MSMInput {
-            points: Some(points_to_run),
-            scalars: scalars_to_run,
+            points: Some(&points_to_run),
+            scalars: &scalars_to_run,
             params: msm_params,
         })?;
 
@@ -400,8 +611,10 @@ fn msm_bls12_377_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: true,
+            precompute_factor: PRECOMPUTE_FACTOR,
             curve: Curve::BLS377,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -419,6 +632,7 @@ fn msm_bls12_377_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
     driver.initialize(msm_params)?;
     driver.start_process(None)?;
@@ -432,8 +646,8 @@ fn msm_bls12_377_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let start_set_data = Instant::now();
     let start_full = Instant::now();
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     let dur_set = start_set_data.elapsed();
@@ -496,8 +710,10 @@ fn msm_bls12_381_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let driver = MSMClient::new(
             MSMInit {
                 mem_type: PointMemoryType::DMA,
-                is_precompute: true,
+                precompute_factor: PRECOMPUTE_FACTOR,
                 curve: Curve::BLS381,
+                group: MSMGroup::G1,
+                tuning: MSMTuning::default(),
             },
             dclient,
         );
@@ -512,6 +728,7 @@ fn msm_bls12_381_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let msm_params = MSMParams {
             nof_elements: msm_size as u32,
             hbm_point_addr: None,
+            tuning: None,
         };
         driver.initialize(msm_params)?;
         driver.start_process(None)?;
@@ -523,8 +740,8 @@ fn msm_bls12_381_precompute_test() -> Result<(), Box<dyn std::error::Error>> {
         let start_set_data = Instant::now();
         let start_full = Instant::now();
         driver.set_data(MSMInput {
-            points: Some(points_to_run),
-            scalars: scalars_to_run,
+            points: Some(&points_to_run),
+            scalars: &scalars_to_run,
             params: msm_params,
         })?;
         // driver.get_api();
@@ -585,8 +802,10 @@ fn msm_bls12_381_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let driver = MSMClient::new(
         MSMInit {
             mem_type: PointMemoryType::DMA,
-            is_precompute: true,
+            precompute_factor: PRECOMPUTE_FACTOR,
             curve: Curve::BLS381,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
         },
         dclient,
     );
@@ -604,6 +823,7 @@ fn msm_bls12_381_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let msm_params = MSMParams {
         nof_elements: msm_size,
         hbm_point_addr: None,
+        tuning: None,
     };
     driver.initialize(msm_params)?;
     driver.start_process(None)?;
@@ -617,8 +837,8 @@ fn msm_bls12_381_precompute_max_test() -> Result<(), Box<dyn std::error::Error>>
     let start_set_data = Instant::now();
     let start_full = Instant::now();
     driver.set_data(MSMInput {
-        points: Some(points),
-        scalars,
+        points: Some(&points),
+        scalars: &scalars,
         params: msm_params,
     })?;
     let dur_set = start_set_data.elapsed();
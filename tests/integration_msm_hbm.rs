@@ -39,8 +39,10 @@ fn hbm_msm_bls12_381_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let driver = MSMClient::new(
             MSMInit {
                 mem_type: PointMemoryType::DMA,
-                is_precompute: true,
+                precompute_factor: PRECOMPUTE_FACTOR,
                 curve: Curve::BLS381,
+                group: MSMGroup::G1,
+                tuning: MSMTuning::default(),
             },
             dclient,
         );
@@ -64,6 +66,7 @@ fn hbm_msm_bls12_381_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let msm_params = MSMParams {
             nof_elements: msm_size as u32,
             hbm_point_addr: Some((hbm_addr, offset)),
+            tuning: None,
         };
 
         driver.initialize(msm_params)?;
@@ -76,7 +79,7 @@ fn hbm_msm_bls12_381_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let start_full = Instant::now();
         driver.set_data(MSMInput {
             points: None,
-            scalars: scalars_to_run,
+            scalars: &scalars_to_run,
             params: msm_params,
         })?;
         driver.get_api();
@@ -147,8 +150,10 @@ fn hbm_msm_bls12_377_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let driver = MSMClient::new(
             MSMInit {
                 mem_type: PointMemoryType::HBM,
-                is_precompute: true,
+                precompute_factor: PRECOMPUTE_FACTOR,
                 curve: Curve::BLS377,
+                group: MSMGroup::G1,
+                tuning: MSMTuning::default(),
             },
             dclient,
         );
@@ -173,6 +178,7 @@ fn hbm_msm_bls12_377_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let msm_params = MSMParams {
             nof_elements: msm_size as u32,
             hbm_point_addr: Some((hbm_addr, offset)),
+            tuning: None,
         };
 
         driver.initialize(msm_params)?;
@@ -185,7 +191,7 @@ fn hbm_msm_bls12_377_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
         let start_full = Instant::now();
         driver.set_data(MSMInput {
             points: None,
-            scalars: scalars_to_run,
+            scalars: &scalars_to_run,
             params: msm_params,
         })?;
         let dur_set = start_set_data.elapsed();
@@ -224,3 +230,155 @@ fn hbm_msm_bls12_377_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
     sleep(Duration::from_secs(1));
     Ok(())
 }
+
+#[test]
+fn hbm_msm_bn254_precomp_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+    let low_exp: u32 = 1;
+    let max_exp: u32 = 1;
+    let base = 2;
+
+    log::debug!("Timer generation start");
+    let start_gen = Instant::now();
+    let (points, scalars, _, results) =
+        msm::input_generator_bn254(Pow::pow(base, max_exp) as usize, PRECOMPUTE_FACTOR);
+    let duration_gen = start_gen.elapsed();
+    log::debug!("Time elapsed in input generation is: {:?}", duration_gen);
+
+    let mut run_results: Vec<RunResults> = Vec::new();
+    for iter in low_exp..=max_exp {
+        let msm_size = Pow::pow(base, iter) as usize;
+        log::debug!("MSM size: {}", msm_size);
+        let points_to_run = &points[0..msm_size * 8 * 64];
+        let scalars_to_run = &scalars[0..msm_size * 32];
+
+        log::info!("Create Driver API instance");
+        let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+        let driver = MSMClient::try_new(
+            MSMInit {
+                mem_type: PointMemoryType::HBM,
+                precompute_factor: PRECOMPUTE_FACTOR,
+                curve: Curve::BN254,
+                group: MSMGroup::G1,
+                tuning: MSMTuning::default(),
+            },
+            dclient,
+        )?;
+        driver.driver_client.reset()?;
+
+        let hbm_addr: u64 = 0x0;
+        let offset: u64 = 0x0;
+        log::debug!("Writing points to HBM");
+        driver.load_data_to_hbm(points_to_run, hbm_addr, offset)?;
+
+        log::info!("Checking MSM core is ready: ");
+        driver.is_msm_engine_ready()?;
+        driver.task_label()?;
+
+        log::info!("Starting to initialize task and set number of elements: ");
+        let msm_params = MSMParams {
+            nof_elements: msm_size as u32,
+            hbm_point_addr: Some((hbm_addr, offset)),
+            tuning: None,
+        };
+
+        driver.initialize(msm_params)?;
+        driver.start_process(None)?;
+
+        log::info!("Starting to calculate MSM: ");
+        let start_set_data = Instant::now();
+        let start_full = Instant::now();
+        driver.set_data(MSMInput {
+            points: None,
+            scalars: scalars_to_run,
+            params: msm_params,
+        })?;
+        let dur_set = start_set_data.elapsed();
+        let start_get = Instant::now();
+        log::info!("Waiting MSM result: ");
+        driver.wait_result()?;
+        let duration_wait = start_get.elapsed();
+
+        let mres = driver.result(None).unwrap().unwrap();
+
+        let duration = start_full.elapsed();
+
+        let pos: usize = if msm_size <= 257 {
+            msm_size
+        } else if msm_size / 256 >= 256 && msm_size > 257 {
+            results.len() - 1_usize
+        } else {
+            msm_size / 256
+        };
+
+        let (is_on_curve, is_eq) =
+            msm::result_check_bn254(mres.result, results[pos - 1], results.clone(), msm_size);
+
+        run_results.push(RunResults {
+            msm_size,
+            on_curve: is_on_curve,
+            correct: is_eq,
+            dur_set_data: dur_set,
+            dur_get_result: duration_wait,
+            dur_full_comput: duration,
+        });
+    }
+
+    log::info!("RESULT: {:?}", run_results);
+    sleep(Duration::from_secs(1));
+    Ok(())
+}
+
+#[test]
+fn hbm_msm_bn254_base_set_test() -> Result<(), Box<dyn std::error::Error>> {
+    env_logger::try_init().expect("Invalid logger initialisation");
+    let id = env::var("ID").unwrap_or_else(|_| 0.to_string());
+    let msm_size = 8;
+
+    let (points, scalars, _, results) = msm::input_generator_bn254(msm_size, PRECOMPUTE_FACTOR);
+
+    log::info!("Create Driver API instance");
+    let dclient = DriverClient::new(&id, DriverConfig::driver_client_cfg(CardType::C1100));
+    let driver = MSMClient::try_new(
+        MSMInit {
+            mem_type: PointMemoryType::HBM,
+            precompute_factor: PRECOMPUTE_FACTOR,
+            curve: Curve::BN254,
+            group: MSMGroup::G1,
+            tuning: MSMTuning::default(),
+        },
+        dclient,
+    )?
+    .with_hbm_verify(HbmVerify::Full);
+    driver.driver_client.reset()?;
+
+    log::debug!("Loading base sets to HBM");
+    let point_size = 8 * 64;
+    let srs = driver.load_base_set("srs", &points[0..msm_size * point_size])?;
+    let pk = driver.load_base_set("pk", &points[0..msm_size * point_size])?;
+    log::info!("Resident base sets: {:?}", driver.base_sets());
+    assert!(srs.region().end <= pk.addr);
+    assert!(driver
+        .load_base_set_at("other", &points[0..point_size], srs.addr)
+        .is_err());
+
+    for (name, nof_elements) in [("srs", msm_size), ("pk", msm_size / 2)] {
+        let mres =
+            driver.run_base_set(name, &scalars[0..nof_elements * 32], Some(0..nof_elements))?;
+        let (is_on_curve, is_eq) = msm::result_check_bn254(
+            mres.result,
+            results[nof_elements - 1],
+            results.clone(),
+            nof_elements,
+        );
+        assert!(is_on_curve && is_eq);
+    }
+
+    let mismatches = driver.verify_resident_bases(HbmVerify::Sampled { nof_blocks: 1 })?;
+    assert!(mismatches.is_empty());
+
+    driver.unload_base_set("srs")?;
+    assert!(driver.run_base_set("srs", &scalars, None).is_err());
+    Ok(())
+}
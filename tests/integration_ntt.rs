@@ -36,7 +36,7 @@ fn ntt_test_correctness() -> Result<(), Box<dyn Error>> {
     log::info!("Starting set NTT data");
     driver.set_data(NTTInput {
         buf_host,
-        data: in_vec,
+        data: &in_vec,
     })?;
     log::info!("Successfully set NTT data");
     driver.driver_client.initialize_cms()?;
@@ -128,7 +128,7 @@ fn ntt_parallel_test_correctness() -> Result<(), Box<dyn Error>> {
         );
         driver.set_data(NTTInput {
             buf_host,
-            data: in_vecs[host_wr_idx_adj].clone(),
+            data: &in_vecs[host_wr_idx_adj],
         })?;
         log::info!("Successfully set NTT data");
         driver.wait_result()?;
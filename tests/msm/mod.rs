@@ -1,23 +1,18 @@
 use ark_bls12_377::{
-    Fq as bls377Fq, Fr as bls377Fr, G1Affine as bls377G1Affine, G1Projective as bls377G1Projective,
+    Fr as bls377Fr, G1Affine as bls377G1Affine, G1Projective as bls377G1Projective,
 };
 use ark_bls12_381::{
-    Fq as bls381Fq, Fr as bls381Fr, G1Affine as bls381G1Affine, G1Projective as bls381G1Projective,
-};
-use ark_bn254::{
-    Fq as bn254Fq, Fr as bn254Fr, G1Affine as bn254G1Affine, G1Projective as bn254G1Projective,
+    Fr as bls381Fr, G1Affine as bls381G1Affine, G1Projective as bls381G1Projective,
 };
+use ark_bn254::{Fr as bn254Fr, G1Affine as bn254G1Affine, G1Projective as bn254G1Projective};
 use ark_ec::{AffineCurve, ProjectiveCurve};
-use ark_ff::{BigInteger, Field, PrimeField, Zero};
+use ark_ff::{BigInteger, PrimeField, Zero};
 use ark_std::UniformRand;
+use ingo_blaze::ingo_msm::{precompute_bases, precomputed_size, MSMCurve};
 
-use ::std::ops::{Add, Mul};
+use ::std::ops::Add;
 use std::{fmt::Display, time::Duration};
 
-const SCALAR_SIZE_BLS377: u32 = 32;
-const SCALAR_SIZE_BLS381: u32 = 32;
-const SCALAR_SIZE_BN254: u32 = 32;
-
 const LARGE_PARAM: usize = 256;
 
 fn get_large_param(nof_elements: usize) -> (bool, usize, usize, usize) {
@@ -80,7 +75,7 @@ pub fn input_generator_bls12_377(
 
     for _ in 0..nof_elems {
         let aff = bls377G1Projective::rand(&mut rng).into_affine();
-        let ext = precompute_base_bls12_377(aff, precompute_factor);
+        let ext = precompute_bases(&[aff], precompute_factor).unwrap();
         bases.extend(ext);
 
         let scalar = bls377Fr::rand(&mut rng);
@@ -98,7 +93,7 @@ pub fn input_generator_bls12_377(
             msm_result = msm_result.add(msm_result);
         }
 
-        buf_base.truncate(rest * 96 * 8);
+        buf_base.truncate(precomputed_size::<bls377G1Affine>(rest, precompute_factor));
         bases.extend(buf_base);
         buf_scalar.truncate(rest * 32);
         scalars.extend(buf_scalar);
@@ -112,28 +107,6 @@ pub fn input_generator_bls12_377(
     (bases, scalars, msm_result, results)
 }
 
-pub fn precompute_base_bls12_377(base: bls377G1Affine, precompute_factor: u32) -> Vec<u8> {
-    let mut bases = vec![];
-    let mut current_point = base;
-    let x_bytes = current_point.x.into_repr().to_bytes_le();
-    let y_bytes = current_point.y.into_repr().to_bytes_le();
-    bases.extend_from_slice(&x_bytes);
-    bases.extend_from_slice(&y_bytes);
-    let two = num_bigint::BigUint::from(2u32);
-    for i in 1..precompute_factor {
-        current_point = base;
-        let coeff = bls377Fr::from(two.pow(SCALAR_SIZE_BLS377 * i));
-        current_point = current_point.mul(coeff).into_affine();
-
-        let x_bytes = current_point.x.into_repr().to_bytes_le();
-        let y_bytes = current_point.y.into_repr().to_bytes_le();
-        bases.extend_from_slice(&x_bytes);
-        bases.extend_from_slice(&y_bytes);
-    }
-
-    bases
-}
-
 pub fn result_check_bls12_377(
     result: Vec<u8>,
     msm_result: bls377G1Projective,
@@ -149,15 +122,13 @@ pub fn result_check_bls12_377(
         msm_res = msm_res.add(results[nof_elements % chunk - 1]);
     }
 
-    let proj_x_field = bls377Fq::from_le_bytes_mod_order(&result[96..144]);
-    let proj_y_field = bls377Fq::from_le_bytes_mod_order(&result[48..96]);
-    let proj_z_field = bls377Fq::from_le_bytes_mod_order(&result[0..48]);
-
-    let z_inv = proj_z_field.inverse().unwrap();
-    let aff_x = proj_x_field.mul(z_inv);
-    let aff_y = proj_y_field.mul(z_inv);
-
-    let point = bls377G1Affine::new(aff_x, aff_y, false);
+    let point = match bls377G1Affine::decode_result(&result) {
+        Ok(point) => point.into_affine(),
+        Err(e) => {
+            log::error!("Invalid result on BLS12_377: {}", e);
+            return (false, false);
+        }
+    };
     log::debug!("Result affine point on BLS12_377: {:}", point.to_string());
     if nof_elements < chunk {
         log::debug!("Expected MSM result: {:}\n", msm_result.into_affine());
@@ -200,7 +171,7 @@ pub fn input_generator_bn254(
 
     for _ in 0..nof_elems {
         let aff = bn254G1Projective::rand(&mut rng).into_affine();
-        let ext = precompute_base_bn254(aff, precompute_factor);
+        let ext = precompute_bases(&[aff], precompute_factor).unwrap();
         bases.extend(ext);
 
         let scalar = bn254Fr::rand(&mut rng);
@@ -218,7 +189,7 @@ pub fn input_generator_bn254(
             msm_result = msm_result.add(msm_result);
         }
 
-        buf_base.truncate(rest * 96 * 8);
+        buf_base.truncate(precomputed_size::<bn254G1Affine>(rest, precompute_factor));
         bases.extend(buf_base);
         buf_scalar.truncate(rest * 32);
         scalars.extend(buf_scalar);
@@ -232,28 +203,6 @@ pub fn input_generator_bn254(
     (bases, scalars, msm_result, results)
 }
 
-pub fn precompute_base_bn254(base: bn254G1Affine, precompute_factor: u32) -> Vec<u8> {
-    let mut bases = vec![];
-    let mut current_point = base;
-    let x_bytes = current_point.x.into_repr().to_bytes_le();
-    let y_bytes = current_point.y.into_repr().to_bytes_le();
-    bases.extend_from_slice(&x_bytes);
-    bases.extend_from_slice(&y_bytes);
-    let two = num_bigint::BigUint::from(2u32);
-    for i in 1..precompute_factor {
-        current_point = base;
-        let coeff = bn254Fr::from(two.pow(SCALAR_SIZE_BN254 * i));
-        current_point = current_point.mul(coeff).into_affine();
-
-        let x_bytes = current_point.x.into_repr().to_bytes_le();
-        let y_bytes = current_point.y.into_repr().to_bytes_le();
-        bases.extend_from_slice(&x_bytes);
-        bases.extend_from_slice(&y_bytes);
-    }
-
-    bases
-}
-
 pub fn result_check_bn254(
     result: Vec<u8>,
     msm_result: bn254G1Projective,
@@ -269,15 +218,13 @@ pub fn result_check_bn254(
         msm_res = msm_res.add(results[nof_elements % chunk - 1]);
     }
 
-    let proj_x_field = bn254Fq::from_le_bytes_mod_order(&result[96..144]);
-    let proj_y_field = bn254Fq::from_le_bytes_mod_order(&result[48..96]);
-    let proj_z_field = bn254Fq::from_le_bytes_mod_order(&result[0..48]);
-
-    let z_inv = proj_z_field.inverse().unwrap();
-    let aff_x = proj_x_field.mul(z_inv);
-    let aff_y = proj_y_field.mul(z_inv);
-
-    let point = bn254G1Affine::new(aff_x, aff_y, false);
+    let point = match bn254G1Affine::decode_result(&result) {
+        Ok(point) => point.into_affine(),
+        Err(e) => {
+            log::error!("Invalid result on BN254: {}", e);
+            return (false, false);
+        }
+    };
     log::debug!("Result affine point on BN254: {:}", point.to_string());
     if nof_elements < chunk {
         log::debug!("Expected MSM result: {:}\n", msm_result.into_affine());
@@ -325,7 +272,7 @@ pub fn input_generator_bls12_381(
 
     for _ in 0..nof_elems {
         let aff = bls381G1Projective::rand(&mut rng).into_affine();
-        let ext = precompute_base_bls12_381(aff, precompute_factor);
+        let ext = precompute_bases(&[aff], precompute_factor).unwrap();
         bases.extend(ext);
 
         let scalar = bls381Fr::rand(&mut rng);
@@ -343,7 +290,7 @@ pub fn input_generator_bls12_381(
             msm_result = msm_result.add(msm_result);
         }
 
-        buf_base.truncate(rest * 96 * 8);
+        buf_base.truncate(precomputed_size::<bls381G1Affine>(rest, precompute_factor));
         bases.extend(buf_base);
         buf_scalar.truncate(rest * 32);
         scalars.extend(buf_scalar);
@@ -357,28 +304,6 @@ pub fn input_generator_bls12_381(
     (bases, scalars, msm_result, results)
 }
 
-pub fn precompute_base_bls12_381(base: bls381G1Affine, precompute_factor: u32) -> Vec<u8> {
-    let mut bases = vec![];
-    let mut current_point = base;
-    let x_bytes = current_point.x.into_repr().to_bytes_le();
-    let y_bytes = current_point.y.into_repr().to_bytes_le();
-    bases.extend_from_slice(&x_bytes);
-    bases.extend_from_slice(&y_bytes);
-    let two = num_bigint::BigUint::from(2u32);
-    for i in 1..precompute_factor {
-        current_point = base;
-        let coeff = bls381Fr::from(two.pow(SCALAR_SIZE_BLS381 * i));
-        current_point = current_point.mul(coeff).into_affine();
-
-        let x_bytes = current_point.x.into_repr().to_bytes_le();
-        let y_bytes = current_point.y.into_repr().to_bytes_le();
-        bases.extend_from_slice(&x_bytes);
-        bases.extend_from_slice(&y_bytes);
-    }
-
-    bases
-}
-
 pub fn result_check_bls12_381(
     result: Vec<u8>,
     msm_result: bls381G1Projective,
@@ -394,15 +319,13 @@ pub fn result_check_bls12_381(
         msm_res = msm_res.add(results[nof_elements % chunk - 1]);
     }
 
-    let proj_x_field = bls381Fq::from_le_bytes_mod_order(&result[96..144]);
-    let proj_y_field = bls381Fq::from_le_bytes_mod_order(&result[48..96]);
-    let proj_z_field = bls381Fq::from_le_bytes_mod_order(&result[0..48]);
-
-    let z_inv = proj_z_field.inverse().unwrap();
-    let aff_x = proj_x_field.mul(z_inv);
-    let aff_y = proj_y_field.mul(z_inv);
-
-    let point = bls381G1Affine::new(aff_x, aff_y, false);
+    let point = match bls381G1Affine::decode_result(&result) {
+        Ok(point) => point.into_affine(),
+        Err(e) => {
+            log::error!("Invalid result on BLS12_381: {}", e);
+            return (false, false);
+        }
+    };
     log::debug!("Result affine point on BLS12_381: {:}", point.to_string());
     if nof_elements < chunk {
         log::debug!("Expected MSM result: {:}\n", msm_result.into_affine());